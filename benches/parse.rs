@@ -0,0 +1,61 @@
+#![feature(test)]
+
+extern crate test;
+
+use soft::{parser::parse_program, Expr, Term};
+use test::{black_box, Bencher};
+
+/// A definition with nested functions, lists, maps, quotes and strings, it's repeated
+/// to build a large source file.
+const DEFINITION: &str = r#"
+; Computes the sum of the squares of a list.
+(def* sum-of-squares
+  (fun* sum-of-squares [xs]
+    (fold (fun* step [acc x] (+ acc (* x x))) 0 xs)))
+
+(def* config {:name "sum-of-squares" :values [1 2 3 4 5] :nested {:a 1.5 :b \c}})
+
+(defmacro* unless (fun* unless [cond body] `(if ,cond nil ,@body)))
+"#;
+
+/// Returns a source file with `n` copies of the definition.
+fn large_source(n: usize) -> String {
+    DEFINITION.repeat(n)
+}
+
+#[bench]
+fn parse_large_source(b: &mut Bencher) {
+    let source = large_source(1000);
+    b.iter(|| parse_program(black_box(&source)).unwrap());
+}
+
+#[bench]
+fn clone_large_source(b: &mut Bencher) {
+    let terms = parse_program(&large_source(1000)).unwrap();
+    b.iter(|| black_box(&terms).clone());
+}
+
+#[bench]
+fn lower_large_source(b: &mut Bencher) {
+    let terms = parse_program(&large_source(1000)).unwrap();
+    b.iter(|| {
+        black_box(&terms)
+            .iter()
+            .cloned()
+            .map(Expr::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    });
+}
+
+#[bench]
+fn unbox_large_source(b: &mut Bencher) {
+    let terms = parse_program(&large_source(1000)).unwrap();
+    b.iter(|| {
+        black_box(&terms)
+            .iter()
+            .cloned()
+            .map(Term::unbox)
+            .collect::<Vec<_>>()
+    });
+}
@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use soft::{
+    parser::{parse_program_lossless, parse_program_with_recovery, parse_sexpr},
+    reader::read_program,
+};
+
+// The reader must never panic, whatever the input is: invalid sources are reported as
+// errors, and the terms that are read can always be printed.
+fuzz_target!(|source: &str| {
+    if let Ok(term) = parse_sexpr(source) {
+        let _ = term.to_string();
+    }
+
+    let _ = parse_program_lossless(source);
+    let _ = parse_program_with_recovery(source);
+    let _ = read_program(source.as_bytes());
+});
@@ -1,19 +1,28 @@
 use std::{
+    cell::RefCell,
     convert::Infallible,
     ops::{ControlFlow, FromResidual, Try},
-    sync::{Arc, RwLock},
+    rc::Rc,
 };
 
 use im::HashMap;
+use miette::Diagnostic;
 use thiserror::Error;
 use Trampoline::{Continue, Done, Raise};
 
-use crate::{keyword, soft_vec, Expr, Literal, SrcPos, Term};
+use crate::{
+    intrinsics::{self, Intrinsic},
+    keyword,
+    number::{BigInt, Ratio},
+    soft_vec,
+    symbol::Symbol,
+    Expr, Literal, SemanticError, SrcPos, Term,
+};
 
 #[derive(Clone)]
 pub struct Definition {
     pub is_macro_definition: bool,
-    pub name: String,
+    pub name: Symbol,
     pub value: Value,
 }
 
@@ -31,7 +40,7 @@ pub struct Fun {
     pub name: Expr,
     pub parameters: Vec<Keyword>,
     pub body: Expr,
-    pub environment: Arc<Environment>,
+    pub environment: Rc<Environment>,
 }
 
 /// Bail out of the current evaluation with an error.
@@ -45,20 +54,30 @@ macro_rules! bail {
 /// value, and is used for both the AST and the runtime.
 #[derive(Clone, Default)]
 pub enum Value {
-    Int(u64),
+    Int(i64),
     Keyword(Keyword),
     String(String),
-    Float(u64),
+    Char(char),
+    Float(f64),
+    BigInt(BigInt),
+    Ratio(Ratio),
     Fun(Fun),
+    Intrinsic(Intrinsic),
     List(Vec<Value>),
+    Map(im::HashMap<MapKey, Value>),
     Apply {
         callee: Box<Value>,
         arguments: Vec<Value>,
+
+        /// The position of the application, the errors raised while it's evaluated
+        /// point at it if they don't have a position of their own.
+        src_pos: SrcPos,
     },
     Def(Keyword, Box<Value>),
     DefMacro(Keyword, Box<Value>),
     Recur(Vec<Value>),
     Quote(Expr),
+    Splice(Box<Value>),
     Ptr(*mut ()),
 
     #[default]
@@ -66,86 +85,321 @@ pub enum Value {
 }
 
 impl Value {
-    /// Reads the values into S-Expressions again
+    /// Reads the values into S-Expressions again, the values that can't be read again,
+    /// like functions and pointers, are read into opaque terms like `#<fun name>`.
     pub fn readback(self) -> Term {
-        todo!()
+        let form = |name: &str, rest: Vec<Value>| {
+            let head = Term::Identifier(name.into());
+            let tail = rest.into_iter().map(Value::readback);
+            Term::List(std::iter::once(head).chain(tail).collect())
+        };
+
+        match self {
+            Value::Int(value) => Term::Int(value),
+            Value::Float(value) => Term::Float(value),
+            Value::BigInt(value) => Term::BigInt(value),
+            Value::Ratio(value) => Term::Ratio(value),
+            Value::String(value) => Term::String(value.into()),
+            Value::Char(value) => Term::Char(value),
+            Value::Keyword(Keyword {
+                text,
+                is_atom: true,
+            }) => Term::Atom(text),
+            Value::Keyword(Keyword {
+                text,
+                is_atom: false,
+            }) => Term::Identifier(text),
+            Value::Fun(fun) => Term::Opaque(format!("#<fun {}>", Term::from(fun.name)).into()),
+            Value::Intrinsic(intrinsic) => {
+                Term::Opaque(format!("#<intrinsic {}>", intrinsic.name).into())
+            }
+            Value::List(elements) => Term::Vec(elements.into_iter().map(Value::readback).collect()),
+            Value::Map(entries) => Term::Map(
+                entries
+                    .into_iter()
+                    .flat_map(|(key, value)| [Value::from(key).readback(), value.readback()])
+                    .collect(),
+            ),
+            Value::Apply {
+                callee, arguments, ..
+            } => Term::List(
+                std::iter::once(callee.readback())
+                    .chain(arguments.into_iter().map(Value::readback))
+                    .collect(),
+            ),
+            Value::Def(name, value) => form("def*", vec![Value::Keyword(name), *value]),
+            Value::DefMacro(name, value) => form("defmacro*", vec![Value::Keyword(name), *value]),
+            Value::Recur(arguments) => form("recur", arguments),
+            Value::Quote(expr) => {
+                Term::List([Term::Identifier("quote".into()), Term::from(expr).unbox()].into())
+            }
+            Value::Splice(value) => form("unquote-splicing", vec![*value]),
+            Value::Ptr(ptr) => Term::Opaque(format!("#<ptr {ptr:p}>").into()),
+            Value::Nil => Term::List([].into()),
+        }
     }
 }
 
+/// A key of a map, only atomic values can be used as keys.
 #[derive(Debug, Hash, Clone, PartialEq, Eq)]
+pub enum MapKey {
+    Int(i64),
+    BigInt(BigInt),
+    Ratio(Ratio),
+    Keyword(Keyword),
+    String(String),
+    Char(char),
+}
+
+impl TryFrom<Value> for MapKey {
+    type Error = EvalError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Int(value) => Ok(MapKey::Int(value)),
+            Value::BigInt(value) => Ok(MapKey::BigInt(value)),
+            Value::Ratio(value) => Ok(MapKey::Ratio(value)),
+            Value::Keyword(keyword) => Ok(MapKey::Keyword(keyword)),
+            Value::String(value) => Ok(MapKey::String(value)),
+            Value::Char(value) => Ok(MapKey::Char(value)),
+            _ => Err(EvalError::ExpectedAtomic(SrcPos::default())),
+        }
+    }
+}
+
+impl From<MapKey> for Value {
+    fn from(key: MapKey) -> Self {
+        match key {
+            MapKey::Int(value) => Value::Int(value),
+            MapKey::BigInt(value) => Value::BigInt(value),
+            MapKey::Ratio(value) => Value::Ratio(value),
+            MapKey::Keyword(keyword) => Value::Keyword(keyword),
+            MapKey::String(value) => Value::String(value),
+            MapKey::Char(value) => Value::Char(value),
+        }
+    }
+}
+
+#[derive(Debug, Hash, Clone, Copy, PartialEq, Eq)]
 pub struct Keyword {
-    pub text: String,
+    pub text: Symbol,
     pub is_atom: bool,
 }
 
 impl Keyword {
     pub fn is_keyword(&self, name: &str) -> bool {
-        self.text == name
+        self.text.as_str() == name
     }
 }
 
 /// The environment in which evaluation takes place.
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct Environment {
     pub global: Value,
     pub expanded: bool,
-    pub frames: Arc<RwLock<im::Vector<Frame>>>,
+    pub frames: Rc<RefCell<im::Vector<Frame>>>,
 }
 
 /// Errors that can occur during expansion.
-#[derive(Error, Debug, Clone)]
+#[derive(Error, Diagnostic, Debug, Clone)]
 pub enum ExpansionError {
     #[error("expected keyword")]
-    ExpectedKeyword,
+    #[diagnostic(
+        code(soft::expansion::expected_keyword),
+        help("names of definitions and parameters should be identifiers")
+    )]
+    ExpectedKeyword(#[label("this should be an identifier")] SrcPos),
+
+    #[error("unquote-splicing outside of a list")]
+    #[diagnostic(
+        code(soft::expansion::unquote_splicing_outside_list),
+        help("`,@` can only be used inside of a quasiquoted list, like `(a ,@b)`")
+    )]
+    UnquoteSplicingOutsideList(#[label("this is spliced outside of a list")] SrcPos),
+
+    #[error("invalid literal")]
+    #[diagnostic(code(soft::expansion::invalid_literal))]
+    InvalidLiteral(#[label("this literal can't be expanded")] SrcPos),
 }
 
 impl From<ExpansionError> for Expr {
     fn from(error: ExpansionError) -> Self {
         match error {
-            ExpansionError::ExpectedKeyword => keyword!("eval.error/expected-keyword"),
+            ExpansionError::ExpectedKeyword(_) => keyword!("eval.error/expected-keyword"),
+            ExpansionError::UnquoteSplicingOutsideList(_) => {
+                keyword!("eval.error/unquote-splicing-outside-list")
+            }
+            ExpansionError::InvalidLiteral(_) => keyword!("eval.error/invalid-literal"),
         }
     }
 }
 
 /// Errors that can occur during evaluation.
-#[derive(Error, Debug, Clone)]
+#[derive(Error, Diagnostic, Debug, Clone)]
 pub enum EvalError {
-    #[error("undefined keyword")]
-    UndefinedKeyword(Keyword),
+    #[error("undefined keyword `{}`", .0.text)]
+    #[diagnostic(
+        code(soft::eval::undefined_keyword),
+        help("define it before using it, like `(def* name value)`")
+    )]
+    UndefinedKeyword(Keyword, #[label("this is not defined")] SrcPos),
 
     #[error("expected fun")]
-    ExpectedFun,
+    #[diagnostic(code(soft::eval::expected_fun), help("only functions can be applied"))]
+    ExpectedFun(#[label("this doesn't apply a function")] SrcPos),
 
     #[error("expected atomic")]
-    ExpectedAtomic,
+    #[diagnostic(code(soft::eval::expected_atomic))]
+    ExpectedAtomic(#[label("this should be atomic")] SrcPos),
 
     #[error("incorrect arity")]
-    IncorrectArity,
+    #[diagnostic(
+        code(soft::eval::incorrect_arity),
+        help("check the number of parameters of the function")
+    )]
+    IncorrectArity(#[label("this has the wrong number of arguments")] SrcPos),
+
+    #[error("expected list")]
+    #[diagnostic(code(soft::eval::expected_list))]
+    ExpectedList(#[label("this should be a list")] SrcPos),
+
+    #[error("expected map")]
+    #[diagnostic(code(soft::eval::expected_map))]
+    ExpectedMap(#[label("this should be a map")] SrcPos),
+
+    #[error("expected number")]
+    #[diagnostic(code(soft::eval::expected_number))]
+    ExpectedNumber(#[label("this should be a number")] SrcPos),
+
+    #[error("expected char")]
+    #[diagnostic(code(soft::eval::expected_char))]
+    ExpectedChar(#[label("this should be a char")] SrcPos),
+
+    #[error("invalid code point {0}")]
+    #[diagnostic(
+        code(soft::eval::invalid_code_point),
+        help("characters are unicode scalar values, up to `0x10FFFF` and without surrogates")
+    )]
+    InvalidCodePoint(i64, #[label("this is not a char")] SrcPos),
+
+    #[error("division by zero")]
+    #[diagnostic(code(soft::eval::division_by_zero))]
+    DivisionByZero(#[label("this divides by zero")] SrcPos),
+}
+
+/// Errors that can occur during expansion and evaluation. They're kept typed so they can
+/// be reported, and are converted into [Expr] to be handled in the language.
+#[derive(Error, Diagnostic, Debug, Clone)]
+pub enum RuntimeError {
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Semantic(#[from] SemanticError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Expansion(#[from] ExpansionError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Eval(#[from] EvalError),
+}
+
+impl ExpansionError {
+    /// Returns the position of the error, to set it.
+    pub fn src_pos_mut(&mut self) -> &mut SrcPos {
+        match self {
+            ExpansionError::ExpectedKeyword(src_pos)
+            | ExpansionError::UnquoteSplicingOutsideList(src_pos)
+            | ExpansionError::InvalidLiteral(src_pos) => src_pos,
+        }
+    }
+}
+
+impl EvalError {
+    /// Returns the position of the error, to set it.
+    pub fn src_pos_mut(&mut self) -> &mut SrcPos {
+        match self {
+            EvalError::UndefinedKeyword(_, src_pos)
+            | EvalError::ExpectedFun(src_pos)
+            | EvalError::ExpectedAtomic(src_pos)
+            | EvalError::IncorrectArity(src_pos)
+            | EvalError::ExpectedList(src_pos)
+            | EvalError::ExpectedMap(src_pos)
+            | EvalError::ExpectedNumber(src_pos)
+            | EvalError::ExpectedChar(src_pos)
+            | EvalError::InvalidCodePoint(_, src_pos)
+            | EvalError::DivisionByZero(src_pos) => src_pos,
+        }
+    }
+}
+
+impl RuntimeError {
+    /// Sets the position of the error if it doesn't have one yet, like the errors of the
+    /// intrinsics, that don't know where they were applied.
+    pub fn with_src_pos(mut self, src_pos: SrcPos) -> Self {
+        let current = match &mut self {
+            RuntimeError::Semantic(_) => None,
+            RuntimeError::Expansion(error) => Some(error.src_pos_mut()),
+            RuntimeError::Eval(error) => Some(error.src_pos_mut()),
+        };
+
+        if let Some(current) = current.filter(|current| **current == SrcPos::default()) {
+            *current = src_pos;
+        }
+
+        self
+    }
+}
+
+impl From<RuntimeError> for Expr {
+    fn from(error: RuntimeError) -> Self {
+        match error {
+            RuntimeError::Semantic(error) => error.into(),
+            RuntimeError::Expansion(error) => error.into(),
+            RuntimeError::Eval(error) => error.into(),
+        }
+    }
 }
 
 impl From<EvalError> for Expr {
     fn from(value: EvalError) -> Self {
         match value {
-            EvalError::UndefinedKeyword(Keyword { text: name, .. }) => {
-                soft_vec!(keyword!("eval.error/expected-keyword"), name)
+            EvalError::UndefinedKeyword(Keyword { text: name, .. }, _) => {
+                soft_vec!(keyword!("eval.error/expected-keyword"), name.to_string())
+            }
+            EvalError::ExpectedFun(_) => keyword!("eval.error/expected-fun"),
+            EvalError::ExpectedAtomic(_) => keyword!("eval.error/expected-atomic"),
+            EvalError::IncorrectArity(_) => keyword!("eval.error/incorrect-arity"),
+            EvalError::ExpectedList(_) => keyword!("eval.error/expected-list"),
+            EvalError::ExpectedMap(_) => keyword!("eval.error/expected-map"),
+            EvalError::ExpectedNumber(_) => keyword!("eval.error/expected-number"),
+            EvalError::DivisionByZero(_) => keyword!("eval.error/division-by-zero"),
+            EvalError::ExpectedChar(_) => keyword!("eval.error/expected-char"),
+            EvalError::InvalidCodePoint(code_point, _) => {
+                soft_vec!(keyword!("eval.error/invalid-code-point"), code_point)
             }
-            EvalError::ExpectedFun => keyword!("eval.error/expected-fun"),
-            EvalError::ExpectedAtomic => keyword!("eval.error/expected-atomic"),
-            EvalError::IncorrectArity => keyword!("eval.error/incorrect-arity"),
         }
     }
 }
 
 /// A trampoline for evaluation. It's treated like a result, but it can also
 /// contain a continuation.
-pub enum Trampoline<T, E = Expr> {
+pub enum Trampoline<T, E = RuntimeError> {
     Done(T),
     Raise(E),
     Continue(Box<dyn Fn() -> Trampoline<T>>),
 }
 
 impl Trampoline<Value> {
-    pub fn eval_into_result(self) -> Result<Value, Expr> {
+    /// Sets the position of the error raised, if it doesn't have one yet.
+    pub fn with_src_pos(self, src_pos: SrcPos) -> Self {
+        match self {
+            Raise(error) => Raise(error.with_src_pos(src_pos)),
+            trampoline => trampoline,
+        }
+    }
+
+    pub fn eval_into_result(self) -> Result<Value, RuntimeError> {
         match self.branch() {
             ControlFlow::Continue(value) => Ok(value),
             ControlFlow::Break(Err(err)) => Err(err),
@@ -160,7 +414,7 @@ impl TryFrom<Value> for Keyword {
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         match value {
             Value::Keyword(keyword) => Ok(keyword),
-            _ => Err(ExpansionError::ExpectedKeyword),
+            _ => Err(ExpansionError::ExpectedKeyword(SrcPos::default())),
         }
     }
 }
@@ -169,7 +423,7 @@ impl Frame {
     /// Set a definition in the frame.
     pub fn insert_definition(&mut self, name: impl Into<Keyword>, value: Value) {
         let keyword: Keyword = name.into();
-        self.definitions.insert(keyword.clone(), Definition {
+        self.definitions.insert(keyword, Definition {
             is_macro_definition: false,
             name: keyword.text,
             value,
@@ -177,11 +431,33 @@ impl Frame {
     }
 }
 
+impl Default for Environment {
+    /// Creates an environment with a root frame, that has the definitions of the
+    /// intrinsics.
+    fn default() -> Self {
+        let mut root = Frame {
+            name: None,
+            src_pos: SrcPos::default(),
+            definitions: im::HashMap::new(),
+            is_catching_scope: false,
+        };
+        for intrinsic in intrinsics::INTRINSICS {
+            root.insert_definition(intrinsic.name, Value::Intrinsic(*intrinsic));
+        }
+
+        Self {
+            global: Value::Nil,
+            expanded: false,
+            frames: Rc::new(RefCell::new(im::vector![root])),
+        }
+    }
+}
+
 impl Environment {
     /// Find a definition in the environment.
     pub fn find_definition(&self, name: impl Into<Keyword>) -> Option<Definition> {
         let name: Keyword = name.into();
-        for frame in self.frames.read().unwrap().iter().rev() {
+        for frame in self.frames.borrow().iter().rev() {
             if let Some(expr) = frame.definitions.get(&name) {
                 return Some(expr.clone());
             }
@@ -192,7 +468,7 @@ impl Environment {
 
     /// Add frame to the environment.
     pub fn push_frame(&self, name: Expr, src_pos: SrcPos) {
-        self.frames.write().unwrap().push_back(Frame {
+        self.frames.borrow_mut().push_back(Frame {
             src_pos,
             name: Some(name),
             definitions: im::HashMap::new(),
@@ -211,19 +487,19 @@ fn associate_parameters(
     let mut environment = im::HashMap::new();
     let vararg_parameter = if len > 2 && parameters[len - 2].is_keyword("&") {
         parameters.remove(parameters.len() - 2); // remove &
-        Some(parameters[parameters.len() - 1].clone())
+        Some(parameters[parameters.len() - 1])
     } else {
         None
     };
 
     for (index, parameter) in parameters.iter().enumerate() {
-        match (arguments.first(), vararg_parameter.clone()) {
+        match (arguments.first(), vararg_parameter) {
             (Some(_), Some(ref parameter)) if index == parameters.len() - 1 => {
-                environment.insert(parameter.clone(), Value::List(arguments));
+                environment.insert(*parameter, Value::List(arguments));
                 break;
             }
-            (None, _) => bail!(EvalError::IncorrectArity),
-            (Some(argument), _) => environment.insert(parameter.clone(), argument.clone()),
+            (None, _) => bail!(EvalError::IncorrectArity(SrcPos::default())),
+            (Some(argument), _) => environment.insert(*parameter, argument.clone()),
         };
 
         arguments.remove(0);
@@ -237,25 +513,28 @@ impl Fun {
     pub fn call(&self, environment: &Environment, arguments: Vec<Value>) -> Trampoline<Value> {
         environment.push_frame(self.name.clone(), SrcPos::default());
 
-        let mut current_environment = self.environment.frames.write().unwrap();
+        let definitions = associate_parameters(self.parameters.clone(), arguments.clone())?;
+        let mut current_environment = self.environment.frames.borrow_mut();
         let frame = current_environment.back_mut().unwrap();
-        for (name, value) in associate_parameters(self.parameters.clone(), arguments.clone())? {
-            frame.definitions.insert(name.clone(), Definition {
+        for (name, value) in definitions {
+            frame.definitions.insert(name, Definition {
                 is_macro_definition: false,
                 name: name.text,
                 value,
             });
         }
+        drop(current_environment);
 
         self.body.clone().expand(environment)?.eval(environment)
     }
 }
 
 /// Expand apply expressions.
-fn apply_expand(apply: crate::Apply, environment: &Environment) -> Result<Value, Expr> {
+fn apply_expand(apply: crate::Apply, environment: &Environment) -> Result<Value, RuntimeError> {
     let callee = apply.callee()?;
+    let src_pos = apply.src_pos();
     if let Expr::Literal(Literal(Term::Identifier(k) | Term::Atom(k))) = callee {
-        return match environment.find_definition(k.clone()) {
+        return match environment.find_definition(k) {
             Some(Definition {
                 name: _,
                 value: Value::Fun(fun),
@@ -270,12 +549,13 @@ fn apply_expand(apply: crate::Apply, environment: &Environment) -> Result<Value,
                 fun.call(environment, arguments).eval_into_result()
             }
             _ => Ok(Value::Apply {
-                callee: Value::Keyword(Keyword::from(k.clone())).into(),
+                callee: Value::Keyword(Keyword::from(k)).into(),
                 arguments: apply
                     .spine()?
                     .into_iter()
                     .map(|expr| expr.expand(environment))
                     .collect::<Result<Vec<_>, _>>()?,
+                src_pos,
             }),
         };
     }
@@ -287,30 +567,103 @@ fn apply_expand(apply: crate::Apply, environment: &Environment) -> Result<Value,
             .into_iter()
             .map(|expr| expr.expand(environment))
             .collect::<Result<Vec<_>, _>>()?,
+        src_pos,
     })
 }
 
 /// Expand fun expressions.
-fn fun_expand(fun: crate::Fun, environment: &Environment) -> Result<Value, Expr> {
+fn fun_expand(fun: crate::Fun, environment: &Environment) -> Result<Value, RuntimeError> {
     Ok(Value::Fun(Fun {
         name: fun.name()?,
         parameters: fun
             .parameters()?
             .elements()?
             .into_iter()
-            .map(|expr| expr.expand(environment))
-            .collect::<Result<Vec<_>, _>>()?
-            .into_iter()
-            .map(|value| value.try_into())
+            .map(|expr| keyword_expand(expr, environment))
             .collect::<Result<Vec<_>, _>>()?,
         body: fun.body()?,
-        environment: Arc::new(environment.clone()),
+        environment: Rc::new(environment.clone()),
     }))
 }
 
+/// Expand the names of definitions and parameters, that should be keywords.
+fn keyword_expand(expr: Expr, environment: &Environment) -> Result<Keyword, RuntimeError> {
+    let src_pos = expr.src_pos();
+    expr.expand(environment)?
+        .try_into()
+        .map_err(|error: ExpansionError| RuntimeError::from(error).with_src_pos(src_pos))
+}
+
+/// Expand quasiquote templates, the `unquote` holes are expanded as expressions to be
+/// evaluated, and everything else is quoted. The `depth` is the number of quasiquotes
+/// nested in the template, only the holes at depth `0` are evaluated.
+fn quasiquote_expand(
+    template: Term,
+    depth: usize,
+    environment: &Environment,
+) -> Result<Value, RuntimeError> {
+    if let Some((head, tail)) = template.split() {
+        if head.is_keyword("unquote") && tail.len() == 1 && depth == 0 {
+            return Expr::try_from(tail[0].clone())?.expand(environment);
+        }
+
+        if head.is_keyword("unquote-splicing") && tail.len() == 1 && depth == 0 {
+            return Err(ExpansionError::UnquoteSplicingOutsideList(template.src_pos()).into());
+        }
+    }
+
+    // The elements of nested quasiquotes are one level deeper, and the ones of the holes
+    // that aren't evaluated are one level shallower.
+    let depth = match template.split() {
+        Some((head, _)) if head.is_keyword("quasiquote") => depth + 1,
+        Some((head, _)) if head.is_keyword("unquote") || head.is_keyword("unquote-splicing") => {
+            depth.saturating_sub(1)
+        }
+        _ => depth,
+    };
+
+    let src_pos = template.src_pos();
+    match template {
+        Term::SrcPos(_, box term) => quasiquote_expand(term, depth, environment),
+        Term::List(elements) | Term::Vec(elements) => {
+            let mut values = Vec::new();
+            for element in elements.iter() {
+                match element.split() {
+                    Some((head, tail))
+                        if head.is_keyword("unquote-splicing") && tail.len() == 1 && depth == 0 =>
+                    {
+                        let value = Expr::try_from(tail[0].clone())?.expand(environment)?;
+                        values.push(Value::Splice(value.into()));
+                    }
+                    _ => values.push(quasiquote_expand(element.clone(), depth, environment)?),
+                }
+            }
+
+            Ok(Value::List(values))
+        }
+        Term::Map(elements) => Ok(Value::Apply {
+            callee: Value::Intrinsic(intrinsics::HASH_MAP).into(),
+            arguments: elements
+                .iter()
+                .map(|element| quasiquote_expand(element.clone(), depth, environment))
+                .collect::<Result<Vec<_>, _>>()?,
+            src_pos,
+        }),
+        Term::Identifier(_) => Ok(Value::Quote(Expr::Literal(Literal(template)))),
+        literal => Expr::Literal(Literal(literal)).expand(environment),
+    }
+}
+
 impl Expr {
-    /// Expand the expression into a value.
-    pub fn expand(self, environment: &Environment) -> Result<Value, Expr> {
+    /// Expand the expression into a value. The errors that are raised without a position
+    /// point at this expression.
+    pub fn expand(self, environment: &Environment) -> Result<Value, RuntimeError> {
+        let src_pos = self.src_pos();
+        self.expand_at(environment)
+            .map_err(|error| error.with_src_pos(src_pos))
+    }
+
+    fn expand_at(self, environment: &Environment) -> Result<Value, RuntimeError> {
         match self {
             Expr::Apply(apply) => apply_expand(apply, environment),
             Expr::Fun(fun) => fun_expand(fun, environment),
@@ -324,8 +677,22 @@ impl Expr {
                     .map(|expr| expr.expand(environment))
                     .collect::<Result<Vec<_>, _>>()?,
             )),
+            Expr::Map(map) => {
+                let mut arguments = Vec::new();
+                for (key, value) in map.entries()? {
+                    arguments.push(key.expand(environment)?);
+                    arguments.push(value.expand(environment)?);
+                }
+
+                // Map literals are built by the intrinsic, so they can't be shadowed.
+                Ok(Value::Apply {
+                    callee: Value::Intrinsic(intrinsics::HASH_MAP).into(),
+                    arguments,
+                    src_pos: map.src_pos(),
+                })
+            }
             Expr::Def(def) => Ok(Value::Def(
-                /* name : */ def.name()?.expand(environment)?.try_into()?,
+                /* name : */ keyword_expand(def.name()?, environment)?,
                 /* value: */ def.value()?.expand(environment)?.into(),
             )),
             Expr::Recur(recur) => Ok(Value::Recur(
@@ -337,57 +704,93 @@ impl Expr {
                     .collect::<Result<Vec<_>, _>>()?,
             )),
             Expr::DefMacro(def_macro) => Ok(Value::DefMacro(
-                /* name : */ def_macro.name()?.expand(environment)?.try_into()?,
+                /* name : */ keyword_expand(def_macro.name()?, environment)?,
                 /* value: */ def_macro.value()?.expand(environment)?.into(),
             )),
 
             // Expansion of literal terms, just wrap them in a value. This is
             // the base case of the expansion.
             Expr::Quote(expr) => Ok(Value::Quote(expr.expr()?)),
+            Expr::Quasiquote(quasiquote) => {
+                quasiquote_expand(quasiquote.template()?, 0, environment)
+            }
             Expr::Literal(Literal(Term::Int(value))) => Ok(Value::Int(value)),
-            Expr::Literal(Literal(Term::String(value))) => Ok(Value::String(value)),
-            Expr::Literal(Literal(Term::Float(_, _))) => todo!(),
+            Expr::Literal(Literal(Term::String(value))) => Ok(Value::String(value.to_string())),
+            Expr::Literal(Literal(Term::Char(value))) => Ok(Value::Char(value)),
+            Expr::Literal(Literal(Term::Float(value))) => Ok(Value::Float(value)),
+            Expr::Literal(Literal(Term::BigInt(value))) => Ok(Value::BigInt(value)),
+            Expr::Literal(Literal(Term::Ratio(value))) => Ok(Value::Ratio(value)),
+            Expr::Literal(Literal(Term::List(elements))) if elements.is_empty() => Ok(Value::Nil),
+            Expr::Literal(Literal(Term::SrcPos(_, box term))) => {
+                Expr::Literal(Literal(term)).expand(environment)
+            }
             Expr::Literal(Literal(ref t @ Term::Identifier(ref n) | ref t @ Term::Atom(ref n))) => {
-                if let Some(definition) = environment.find_definition(n.clone()) {
+                if let Some(definition) = environment.find_definition(*n) {
                     if definition.is_macro_definition {
                         return Ok(definition.value.clone());
                     }
                 }
 
                 Ok(Value::Keyword(Keyword {
-                    text: n.clone(),
+                    text: *n,
                     is_atom: matches!(t, Term::Atom(_)),
                 }))
             }
-            Expr::Literal(_) => Err(keyword!("eval.error/invalid-literal")),
+            Expr::Literal(literal) => Err(ExpansionError::InvalidLiteral(literal.src_pos()).into()),
         }
     }
 }
 
+/// Evaluate applications of functions and intrinsics.
+fn apply_eval(
+    callee: Value,
+    arguments: Vec<Value>,
+    environment: &Environment,
+) -> Trampoline<Value> {
+    match callee.eval(environment)? {
+        Value::Fun(fun) => {
+            let mut new_arguments = Vec::new();
+            for argument in arguments {
+                new_arguments.push(argument.eval(environment)?);
+            }
+            fun.call(environment, new_arguments)
+        }
+        Value::Intrinsic(intrinsic) => {
+            let mut new_arguments = Vec::new();
+            for argument in arguments {
+                new_arguments.push(argument.eval(environment)?);
+            }
+            Done((intrinsic.call)(new_arguments)?)
+        }
+        _ => bail!(EvalError::ExpectedFun(SrcPos::default())),
+    }
+}
+
 impl Value {
     /// Evaluate the expression into a value.
     pub fn eval(self, environment: &Environment) -> Trampoline<Value> {
         match self {
             Value::Keyword(keyword) if !keyword.is_atom => {
-                match environment.find_definition(keyword.clone()) {
+                match environment.find_definition(keyword) {
                     Some(Definition { value, .. }) => Done(value),
-                    None => bail!(EvalError::UndefinedKeyword(keyword)),
+                    None => bail!(EvalError::UndefinedKeyword(keyword, SrcPos::default())),
                 }
             }
-            Value::Apply { callee, arguments } => match callee.eval(environment)? {
-                Value::Fun(fun) => {
-                    let mut new_arguments = Vec::new();
-                    for argument in arguments {
-                        new_arguments.push(argument.eval(environment)?);
-                    }
-                    fun.call(environment, new_arguments)
-                }
-                _ => bail!(EvalError::ExpectedFun),
-            },
+            Value::Apply {
+                callee,
+                arguments,
+                src_pos,
+            } => apply_eval(*callee, arguments, environment).with_src_pos(src_pos),
             Value::List(old_elements) => {
                 let mut new_elements = Vec::new();
                 for element in old_elements {
-                    new_elements.push(element.eval(environment)?);
+                    match element {
+                        Value::Splice(value) => match value.eval(environment)? {
+                            Value::List(elements) => new_elements.extend(elements),
+                            _ => bail!(EvalError::ExpectedList(SrcPos::default())),
+                        },
+                        element => new_elements.push(element.eval(environment)?),
+                    }
                 }
 
                 Done(Value::List(new_elements))
@@ -401,16 +804,16 @@ impl Value {
     }
 }
 
-impl<T> Try for Trampoline<T, Expr> {
+impl<T> Try for Trampoline<T, RuntimeError> {
     type Output = T;
-    type Residual = Result<Infallible, Expr>;
+    type Residual = Result<Infallible, RuntimeError>;
 
     fn from_output(output: Self::Output) -> Self {
         Done(output)
     }
 
     fn branch(self) -> ControlFlow<Self::Residual, Self::Output> {
-        let mut value: Trampoline<T, Expr> = self;
+        let mut value: Trampoline<T, RuntimeError> = self;
         loop {
             match value {
                 Done(done) => return ControlFlow::Continue(done),
@@ -432,8 +835,8 @@ impl<T, E, F: From<E>> FromResidual<Result<Infallible, E>> for Trampoline<T, F>
     }
 }
 
-impl From<String> for Keyword {
-    fn from(name: String) -> Self {
+impl From<Symbol> for Keyword {
+    fn from(name: Symbol) -> Self {
         Self {
             text: name,
             is_atom: false,
@@ -441,11 +844,233 @@ impl From<String> for Keyword {
     }
 }
 
+impl From<String> for Keyword {
+    fn from(name: String) -> Self {
+        Symbol::from(name).into()
+    }
+}
+
 impl From<&str> for Keyword {
     fn from(name: &str) -> Self {
-        Self {
-            text: name.to_string(),
-            is_atom: false,
-        }
+        Symbol::from(name).into()
+    }
+}
+
+/// Tests for expansion and evaluation of expressions.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_sexpr;
+
+    fn run(source: &str) -> Result<Value, Expr> {
+        let environment = Environment::default();
+        let expr = Expr::try_from(parse_sexpr(source)?)?;
+        run_expr(expr, &environment).map_err(Expr::from)
+    }
+
+    fn run_expr(expr: Expr, environment: &Environment) -> Result<Value, RuntimeError> {
+        expr.expand(environment)?
+            .eval(environment)
+            .eval_into_result()
+    }
+
+    #[test]
+    fn evaluates_float() {
+        assert!(matches!(run("1.5e2"), Ok(Value::Float(value)) if value == 150.0));
+    }
+
+    #[test]
+    fn evaluates_quasiquote() {
+        let Ok(Value::List(elements)) = run("`(a ,1.5 ,@[2 3] 4)") else {
+            panic!("expected list");
+        };
+
+        assert!(matches!(elements.as_slice(), [
+            Value::Quote(_),
+            Value::Float(_),
+            Value::Int(2),
+            Value::Int(3),
+            Value::Int(4)
+        ]));
+    }
+
+    #[test]
+    fn reads_back_values() {
+        let readback = |source: &str| run(source).ok().map(|value| value.readback().unbox());
+
+        assert_eq!(readback("42"), Some(Term::Int(42)));
+        assert_eq!(readback(":a"), Some(Term::Atom("a".into())));
+        assert_eq!(
+            readback("'(f x)"),
+            parse_sexpr("(quote (f x))").ok().map(Term::unbox)
+        );
+        assert_eq!(
+            readback("`(a ,@[1 \"b\"] 2.5)"),
+            parse_sexpr("[(quote a) 1 \"b\" 2.5]").ok().map(Term::unbox)
+        );
+        assert_eq!(
+            readback("`{:a ,(+ 1 2)}"),
+            parse_sexpr("{:a 3}").ok().map(Term::unbox)
+        );
+        assert_eq!(
+            readback("``(a ,,(+ 1 2) ,@b)"),
+            parse_sexpr(
+                "[(quote quasiquote) [(quote a) [(quote unquote) 3] [(quote unquote-splicing) (quote b)]]]"
+            )
+            .ok()
+            .map(Term::unbox)
+        );
+        assert_eq!(
+            readback("(fun* f [x] x)").map(|term| term.to_string()),
+            Some("#<fun f>".to_string())
+        );
+        assert!(matches!(readback("get"), Some(Term::Opaque(_))));
+        assert_eq!(Value::Nil.readback().to_string(), "()");
+
+        // Nil is read back into a form that evaluates to nil again.
+        let nil = readback("(get {} :a)").unwrap();
+        assert_eq!(readback(&nil.to_string()), Some(nil));
+    }
+
+    #[test]
+    fn evaluates_maps() {
+        let readback = |source: &str| run(source).ok().map(|value| value.readback().unbox());
+        let parse = |source: &str| parse_sexpr(source).ok().map(Term::unbox);
+
+        assert_eq!(readback("{:a {\"b\" 2}}"), parse("{:a {\"b\" 2}}"));
+        assert_eq!(readback("(get {:a 1 :b 2} :b)"), parse("2"));
+        assert_eq!(readback("(get {:a 1} :c)"), parse("()"));
+        assert_eq!(readback("(get {:a 1} :c 3)"), parse("3"));
+        assert_eq!(readback("(get (assoc {} 1 :x) 1)"), parse(":x"));
+        assert_eq!(readback("(dissoc {:a 1} :a)"), parse("{}"));
+        assert_eq!(readback("(keys {:a 1})"), parse("[:a]"));
+        assert_eq!(readback("(vals {:a 1})"), parse("[1]"));
+        assert_eq!(readback("(contains? {:a 1} :a)"), parse(":true"));
+        assert_eq!(readback("(contains? {:a 1} :b)"), parse(":false"));
+    }
+
+    #[test]
+    fn fails_on_invalid_map_arguments() {
+        assert!(run("(get [1] 0)").is_err());
+        assert!(run("(assoc {} :a)").is_err());
+        assert!(run("{[1] 2}").is_err());
+    }
+
+    #[test]
+    fn evaluates_arithmetic() {
+        let readback = |source: &str| run(source).ok().map(|value| value.readback().unbox());
+
+        assert_eq!(readback("(+)"), Some(Term::Int(0)));
+        assert_eq!(readback("(+ 1 2 -3)"), Some(Term::Int(0)));
+        assert_eq!(readback("(- 5)"), Some(Term::Int(-5)));
+        assert_eq!(readback("(- 1 2 3)"), Some(Term::Int(-4)));
+        assert_eq!(readback("(* 2 -3 4)"), Some(Term::Int(-24)));
+        assert_eq!(readback("(/ 6 -3)"), Some(Term::Int(-2)));
+        assert_eq!(readback("(mod -7 2)"), Some(Term::Int(1)));
+        assert_eq!(readback("(rem -7 2)"), Some(Term::Int(-1)));
+        assert_eq!(readback("(mod 7 -2)"), Some(Term::Int(-1)));
+        assert_eq!(readback("(+ 1 0.5)"), Some(Term::Float(1.5)));
+    }
+
+    #[test]
+    fn evaluates_numeric_tower() {
+        let print = |source: &str| run(source).ok().map(|value| value.readback().to_string());
+
+        assert_eq!(
+            print("(+ 9223372036854775807 1)").as_deref(),
+            Some("9223372036854775808")
+        );
+        assert_eq!(
+            print("(- -9223372036854775808)").as_deref(),
+            Some("9223372036854775808")
+        );
+        assert_eq!(
+            print("(* 9223372036854775807 9223372036854775807 -1)").as_deref(),
+            Some("-85070591730234615847396907784232501249")
+        );
+        assert_eq!(
+            print("(- (+ 9223372036854775807 1) 1)").as_deref(),
+            Some("9223372036854775807")
+        );
+        assert_eq!(print("(/ -7 2)").as_deref(), Some("-7/2"));
+        assert_eq!(print("(+ (/ 1 3) (/ 2 3))").as_deref(), Some("1"));
+        assert_eq!(print("(mod (/ 7 2) 2)").as_deref(), Some("3/2"));
+        assert_eq!(print("(* (/ 1 2) 1.5)").as_deref(), Some("0.75"));
+        assert_eq!(
+            print("(< 1 (/ 3 2) 2.5 (+ 9223372036854775807 1))").as_deref(),
+            Some(":true")
+        );
+        assert_eq!(print("(== 2 (/ 4 2) 2.0)").as_deref(), Some(":true"));
+        assert_eq!(print("(>= 1 2)").as_deref(), Some(":false"));
+    }
+
+    #[test]
+    fn fails_on_invalid_arithmetic() {
+        let error = |source: &str| match run(source) {
+            Err(error) => Term::from(error).unbox(),
+            Ok(_) => panic!("expected error"),
+        };
+        let keyword = |name: &str| Term::Atom(name.into());
+
+        assert_eq!(error("(/ 1 0)"), keyword("eval.error/division-by-zero"));
+        assert_eq!(error("(mod 1 0)"), keyword("eval.error/division-by-zero"));
+        assert_eq!(error("(+ 1 :a)"), keyword("eval.error/expected-number"));
+    }
+
+    #[test]
+    fn evaluates_chars() {
+        let print = |source: &str| run(source).ok().map(|value| value.readback().to_string());
+
+        assert_eq!(print("\\a").as_deref(), Some("\\a"));
+        assert_eq!(print("(char->int \\u{1F600})").as_deref(), Some("128512"));
+        assert_eq!(print("(int->char 10)").as_deref(), Some("\\newline"));
+        assert_eq!(print("(char/upper \\é)").as_deref(), Some("\\É"));
+        assert_eq!(print("(char/upper \\ß)").as_deref(), Some("\\ß"));
+        assert_eq!(print("(char/alpha? \\a)").as_deref(), Some(":true"));
+        assert_eq!(print("(char/alpha? \\1)").as_deref(), Some(":false"));
+        assert!(run("(int->char 55296)").is_err());
+        assert!(run("(int->char -1)").is_err());
+        assert!(run("(char->int 1)").is_err());
+    }
+
+    #[test]
+    fn fails_with_positions() {
+        let error = |source: &str| {
+            let environment = Environment::default();
+            let expr = Expr::try_from(parse_sexpr(source).unwrap()).unwrap();
+            match run_expr(expr, &environment) {
+                Err(error) => error,
+                Ok(_) => panic!("expected error"),
+            }
+        };
+
+        let RuntimeError::Eval(EvalError::DivisionByZero(src_pos)) = error("(+ 1 (/ 2 0))") else {
+            panic!("expected division by zero");
+        };
+        assert_eq!(src_pos.byte, 5..12);
+
+        let RuntimeError::Eval(EvalError::UndefinedKeyword(_, src_pos)) = error("[1 (foo)]") else {
+            panic!("expected undefined keyword");
+        };
+        assert_eq!(src_pos.byte, 3..8);
+
+        let RuntimeError::Expansion(ExpansionError::ExpectedKeyword(src_pos)) =
+            error("(fun* f [a 1] a)")
+        else {
+            panic!("expected keyword");
+        };
+        assert_eq!(src_pos.byte, 11..12);
+
+        let RuntimeError::Expansion(ExpansionError::UnquoteSplicingOutsideList(src_pos)) =
+            error("(list `,@[1])")
+        else {
+            panic!("expected splice outside list");
+        };
+        assert_eq!(src_pos.byte, 7..12);
+    }
+
+    #[test]
+    fn fails_on_splice_outside_list() {
+        assert!(run("`,@[1 2]").is_err());
     }
 }
@@ -0,0 +1,357 @@
+use std::cmp::Ordering;
+
+use crate::{
+    eval::{EvalError, Keyword, MapKey, Value},
+    number::Number,
+    symbol::Symbol,
+    SrcPos,
+};
+
+/// A function implemented in Rust, it receives the already evaluated arguments.
+#[derive(Clone, Copy)]
+pub struct Intrinsic {
+    pub name: &'static str,
+    pub call: fn(Vec<Value>) -> Result<Value, EvalError>,
+}
+
+/// Builds a map from its keys and values, map literals like `{:a 1}` are expanded into it.
+pub const HASH_MAP: Intrinsic = Intrinsic {
+    name: "hash-map",
+    call: hash_map,
+};
+
+/// Every intrinsic, they're defined in the root frame of the environment.
+pub const INTRINSICS: &[Intrinsic] = &[
+    HASH_MAP,
+    Intrinsic {
+        name: "get",
+        call: get,
+    },
+    Intrinsic {
+        name: "assoc",
+        call: assoc,
+    },
+    Intrinsic {
+        name: "dissoc",
+        call: dissoc,
+    },
+    Intrinsic {
+        name: "keys",
+        call: keys,
+    },
+    Intrinsic {
+        name: "vals",
+        call: vals,
+    },
+    Intrinsic {
+        name: "contains?",
+        call: contains,
+    },
+    Intrinsic {
+        name: "+",
+        call: add,
+    },
+    Intrinsic {
+        name: "-",
+        call: sub,
+    },
+    Intrinsic {
+        name: "*",
+        call: mul,
+    },
+    Intrinsic {
+        name: "/",
+        call: div,
+    },
+    Intrinsic {
+        name: "mod",
+        call: modulo,
+    },
+    Intrinsic {
+        name: "rem",
+        call: rem,
+    },
+    Intrinsic {
+        name: "char->int",
+        call: char_to_int,
+    },
+    Intrinsic {
+        name: "int->char",
+        call: int_to_char,
+    },
+    Intrinsic {
+        name: "char/upper",
+        call: char_upper,
+    },
+    Intrinsic {
+        name: "char/alpha?",
+        call: char_alpha,
+    },
+    Intrinsic {
+        name: "==",
+        call: equal,
+    },
+    Intrinsic {
+        name: "<",
+        call: less,
+    },
+    Intrinsic {
+        name: ">",
+        call: greater,
+    },
+    Intrinsic {
+        name: "<=",
+        call: less_equal,
+    },
+    Intrinsic {
+        name: ">=",
+        call: greater_equal,
+    },
+];
+
+/// Converts a boolean into the atoms `:true` or `:false`.
+fn boolean(value: bool) -> Value {
+    Value::Keyword(Keyword {
+        text: Symbol::intern(&value.to_string()),
+        is_atom: true,
+    })
+}
+
+/// Expects exactly `N` arguments.
+fn expect_arguments<const N: usize>(arguments: Vec<Value>) -> Result<[Value; N], EvalError> {
+    arguments
+        .try_into()
+        .map_err(|_| EvalError::IncorrectArity(SrcPos::default()))
+}
+
+/// Splits the map from the rest of the arguments.
+fn expect_map(
+    mut arguments: Vec<Value>,
+) -> Result<(im::HashMap<MapKey, Value>, Vec<Value>), EvalError> {
+    if arguments.is_empty() {
+        return Err(EvalError::IncorrectArity(SrcPos::default()));
+    }
+
+    match arguments.remove(0) {
+        Value::Map(map) => Ok((map, arguments)),
+        _ => Err(EvalError::ExpectedMap(SrcPos::default())),
+    }
+}
+
+/// Inserts every key followed by its value into the map.
+fn insert_entries(
+    mut map: im::HashMap<MapKey, Value>,
+    entries: Vec<Value>,
+) -> Result<Value, EvalError> {
+    if !entries.len().is_multiple_of(2) {
+        return Err(EvalError::IncorrectArity(SrcPos::default()));
+    }
+
+    let mut entries = entries.into_iter();
+    while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+        map.insert(key.try_into()?, value);
+    }
+
+    Ok(Value::Map(map))
+}
+
+/// `(hash-map k v ...)`
+fn hash_map(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    insert_entries(im::HashMap::new(), arguments)
+}
+
+/// `(get map key)` or `(get map key default)`, the default is nil, that's read back as `()`.
+fn get(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    let (map, arguments) = expect_map(arguments)?;
+    let [key, default] = match arguments.len() {
+        1 => [arguments[0].clone(), Value::Nil],
+        _ => expect_arguments(arguments)?,
+    };
+
+    Ok(map.get(&key.try_into()?).cloned().unwrap_or(default))
+}
+
+/// `(assoc map k v ...)`
+fn assoc(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    let (map, entries) = expect_map(arguments)?;
+    insert_entries(map, entries)
+}
+
+/// `(dissoc map k ...)`
+fn dissoc(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    let (mut map, keys) = expect_map(arguments)?;
+    for key in keys {
+        map.remove(&key.try_into()?);
+    }
+
+    Ok(Value::Map(map))
+}
+
+/// `(keys map)`
+fn keys(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    let (map, rest) = expect_map(arguments)?;
+    expect_arguments::<0>(rest)?;
+    Ok(Value::List(map.keys().cloned().map(Value::from).collect()))
+}
+
+/// `(vals map)`
+fn vals(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    let (map, rest) = expect_map(arguments)?;
+    expect_arguments::<0>(rest)?;
+    Ok(Value::List(map.values().cloned().collect()))
+}
+
+/// `(contains? map key)`
+fn contains(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    let (map, rest) = expect_map(arguments)?;
+    let [key] = expect_arguments(rest)?;
+    Ok(boolean(map.contains_key(&key.try_into()?)))
+}
+
+/// Folds the numbers from left to right.
+fn fold_numbers(
+    arguments: Vec<Value>,
+    operation: fn(Number, Number) -> Result<Number, EvalError>,
+) -> Result<Value, EvalError> {
+    let mut numbers = arguments.into_iter().map(Number::try_from);
+    let mut result = numbers
+        .next()
+        .ok_or(EvalError::IncorrectArity(SrcPos::default()))??;
+    for number in numbers {
+        result = operation(result, number?)?;
+    }
+
+    Ok(result.into())
+}
+
+/// Checks that every pair of adjacent numbers is in the expected order.
+fn compare_numbers(
+    arguments: Vec<Value>,
+    predicate: fn(Ordering) -> bool,
+) -> Result<Value, EvalError> {
+    let numbers = arguments
+        .into_iter()
+        .map(Number::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    if numbers.is_empty() {
+        return Err(EvalError::IncorrectArity(SrcPos::default()));
+    }
+
+    Ok(boolean(numbers.windows(2).all(|pair| {
+        pair[0].partial_cmp(&pair[1]).is_some_and(predicate)
+    })))
+}
+
+/// `(+ n ...)`, the sum of no numbers is `0`.
+fn add(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    if arguments.is_empty() {
+        return Ok(Value::Int(0));
+    }
+
+    fold_numbers(arguments, |a, b| Ok(a + b))
+}
+
+/// `(- n ...)`, with a single number it's negated.
+fn sub(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    if let [number] = arguments.as_slice() {
+        return Ok((-Number::try_from(number.clone())?).into());
+    }
+
+    fold_numbers(arguments, |a, b| Ok(a - b))
+}
+
+/// `(* n ...)`, the product of no numbers is `1`.
+fn mul(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    if arguments.is_empty() {
+        return Ok(Value::Int(1));
+    }
+
+    fold_numbers(arguments, |a, b| Ok(a * b))
+}
+
+/// `(/ n m ...)`, integers are divided exactly, into a ratio if needed.
+fn div(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    if arguments.len() < 2 {
+        return Err(EvalError::IncorrectArity(SrcPos::default()));
+    }
+
+    fold_numbers(arguments, Number::divide)
+}
+
+/// `(mod n m)`, the remainder has the sign of the divisor.
+fn modulo(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    fold_numbers(Vec::from(expect_arguments::<2>(arguments)?), Number::modulo)
+}
+
+/// `(rem n m)`, the remainder has the sign of the dividend.
+fn rem(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    fold_numbers(
+        Vec::from(expect_arguments::<2>(arguments)?),
+        Number::remainder,
+    )
+}
+
+/// `(== n ...)`, compares the values of the numbers, like `(== 1 1.0)`.
+fn equal(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    compare_numbers(arguments, Ordering::is_eq)
+}
+
+/// `(< n ...)`
+fn less(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    compare_numbers(arguments, Ordering::is_lt)
+}
+
+/// `(> n ...)`
+fn greater(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    compare_numbers(arguments, Ordering::is_gt)
+}
+
+/// `(<= n ...)`
+fn less_equal(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    compare_numbers(arguments, Ordering::is_le)
+}
+
+/// `(>= n ...)`
+fn greater_equal(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    compare_numbers(arguments, Ordering::is_ge)
+}
+
+/// Expects a single char argument.
+fn expect_char(arguments: Vec<Value>) -> Result<char, EvalError> {
+    match expect_arguments(arguments)? {
+        [Value::Char(c)] => Ok(c),
+        _ => Err(EvalError::ExpectedChar(SrcPos::default())),
+    }
+}
+
+/// `(char->int c)`, returns the code point of the char.
+fn char_to_int(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    Ok(Value::Int(expect_char(arguments)? as i64))
+}
+
+/// `(int->char n)`, returns the char of the code point.
+fn int_to_char(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    match expect_arguments(arguments)? {
+        [Value::Int(code_point)] => u32::try_from(code_point)
+            .ok()
+            .and_then(char::from_u32)
+            .map(Value::Char)
+            .ok_or(EvalError::InvalidCodePoint(code_point, SrcPos::default())),
+        _ => Err(EvalError::ExpectedNumber(SrcPos::default())),
+    }
+}
+
+/// `(char/upper c)`, the chars that are uppercased into many chars, like `ß`, are kept.
+fn char_upper(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    let c = expect_char(arguments)?;
+    let mut upper = c.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(upper), None) => Ok(Value::Char(upper)),
+        _ => Ok(Value::Char(c)),
+    }
+}
+
+/// `(char/alpha? c)`
+fn char_alpha(arguments: Vec<Value>) -> Result<Value, EvalError> {
+    Ok(boolean(expect_char(arguments)?.is_alphabetic()))
+}
@@ -0,0 +1,680 @@
+use std::{iter::Peekable, str::Chars};
+
+use crate::{
+    number::{BigInt, Number, Ratio},
+    parser::ParseError,
+    source::FileId,
+    SrcPos,
+};
+
+pub fn is_identifier_char(c: char) -> bool {
+    !c.is_whitespace() && !matches!(c, '(' | ')' | '[' | ']' | '{' | '}' | '"' | ';' | '`' | ',')
+}
+
+/// The kind of a token, literals are already decoded into their values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    LParen,          // (
+    RParen,          // )
+    LBracket,        // [
+    RBracket,        // ]
+    LBrace,          // {
+    RBrace,          // }
+    Quote,           // '
+    Quasiquote,      // `
+    Unquote,         // ,
+    UnquoteSplicing, // ,@
+    String(String),  // "some stuff"
+    Char(char),      // \a
+    Atom(String),    // :bla
+    Identifier(String),
+    Int(i64),
+    BigInt(BigInt), // 9223372036854775808
+    Ratio(Ratio),   // 1/2
+    Float(f64),
+    Comment(String), // ; comment or #| comment |#
+    DatumComment,    // #_, comments the next form
+    Whitespace,
+
+    /// An invalid token, like a string with an invalid escape, the lexer continues right
+    /// after it.
+    Error(ParseError),
+}
+
+/// A token with its position in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub src_pos: SrcPos,
+}
+
+/// Tokenizer of the Soft programming language, it's an iterator of every [Token] in the
+/// source, including comments and whitespace, so it never fails.
+pub struct Lexer<'a> {
+    pub peekable: Peekable<Chars<'a>>,
+    pub string: &'a str,
+    pub index: usize,
+    pub file: FileId,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(string: &'a str) -> Self {
+        Self::with_file(FileId::default(), string)
+    }
+
+    /// Creates a lexer for the text of a file registered in the [crate::source::SourceMap].
+    pub fn with_file(file: FileId, string: &'a str) -> Self {
+        Self {
+            peekable: string.chars().peekable(),
+            string,
+            index: 0,
+            file,
+        }
+    }
+
+    pub fn bump(&mut self) -> Option<char> {
+        let c = self.peekable.next()?;
+        self.index += c.len_utf8();
+        Some(c)
+    }
+
+    pub fn peek(&mut self) -> Option<char> {
+        self.peekable.peek().copied()
+    }
+
+    pub fn accumulate(&mut self, mut f: impl FnMut(char) -> bool) -> String {
+        let mut string = String::new();
+
+        loop {
+            match self.peek() {
+                Some(c) if f(c) => string.push(self.bump().unwrap()),
+                _ => break,
+            }
+        }
+
+        string
+    }
+
+    /// Creates a position from `start` until the current index.
+    fn src_pos(&self, start: usize) -> SrcPos {
+        SrcPos {
+            byte: start..self.index,
+            file: self.file,
+        }
+    }
+
+    /// Lexes a string literal, it can span multiple lines and contain the escape sequences
+    /// `\"`, `\\`, `\n`, `\t`, `\r` and `\u{...}`.
+    fn lex_string(&mut self) -> Result<TokenKind, ParseError> {
+        let start = self.index;
+        self.bump();
+        let mut string = String::new();
+        let mut error = None;
+
+        // Invalid escapes don't stop the scanning, so the whole literal is consumed and
+        // the parser can recover after it.
+        loop {
+            match self.bump() {
+                Some('"') => break,
+                Some('\\') => match self.lex_escape() {
+                    Ok(c) => string.push(c),
+                    Err(escape_error) => {
+                        error.get_or_insert(escape_error);
+                    }
+                },
+                Some(c) => string.push(c),
+                None => return Err(ParseError::UnterminatedString(self.src_pos(start))),
+            }
+        }
+
+        match error {
+            Some(error) => Err(error),
+            None => Ok(TokenKind::String(string)),
+        }
+    }
+
+    /// Lexes a block comment like `#| comment |#`, they can be nested, so a block of code
+    /// with comments can be commented out.
+    fn lex_block_comment(&mut self) -> Result<TokenKind, ParseError> {
+        let start = self.index;
+        let mut depth = 0;
+
+        loop {
+            let rest = &self.string[self.index..];
+            if rest.starts_with("#|") {
+                depth += 1;
+            } else if rest.starts_with("|#") {
+                depth -= 1;
+            } else if self.bump().is_some() {
+                continue;
+            } else {
+                return Err(ParseError::UnterminatedComment(self.src_pos(start)));
+            }
+
+            self.bump();
+            self.bump();
+            if depth == 0 {
+                return Ok(TokenKind::Comment(
+                    self.string[start..self.index].to_string(),
+                ));
+            }
+        }
+    }
+
+    /// Lexes a character literal, like `\\a`, or `\\u{1F600}` with its code point. The
+    /// whitespace characters have names, like `\\newline` or `\\space`.
+    fn lex_char(&mut self) -> Result<TokenKind, ParseError> {
+        let start = self.index;
+        self.bump();
+
+        if self.string[self.index..].starts_with("u{") {
+            self.bump();
+            self.bump();
+            let digits = self.accumulate(|c| c.is_ascii_hexdigit());
+            let c = u32::from_str_radix(&digits, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .filter(|_| self.peek() == Some('}'));
+            self.accumulate(|c| c != '}' && is_identifier_char(c));
+            if self.peek() == Some('}') {
+                self.bump();
+            }
+
+            return c
+                .map(TokenKind::Char)
+                .ok_or(ParseError::InvalidCharacter(self.src_pos(start)));
+        }
+
+        // The first character can be anything, like `\\(`, but names are identifiers.
+        let Some(first) = self.bump() else {
+            return Err(ParseError::InvalidCharacter(self.src_pos(start)));
+        };
+        let rest = self.accumulate(is_identifier_char);
+        if rest.is_empty() {
+            return Ok(TokenKind::Char(first));
+        }
+
+        match format!("{first}{rest}").as_str() {
+            "newline" => Ok(TokenKind::Char('\n')),
+            "space" => Ok(TokenKind::Char(' ')),
+            "tab" => Ok(TokenKind::Char('\t')),
+            "return" => Ok(TokenKind::Char('\r')),
+            _ => Err(ParseError::InvalidCharacter(self.src_pos(start))),
+        }
+    }
+
+    /// Lexes the escape sequence after a `\` inside of a string literal.
+    fn lex_escape(&mut self) -> Result<char, ParseError> {
+        let start = self.index - 1;
+
+        let c = match self.bump() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('u') if self.peek() == Some('{') => {
+                self.bump();
+                let digits = self.accumulate(|c| c.is_ascii_hexdigit());
+                let c = u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .filter(|_| self.peek() == Some('}'));
+
+                match c {
+                    Some(c) => {
+                        self.bump();
+                        c
+                    }
+                    None => return Err(ParseError::InvalidEscape(self.src_pos(start))),
+                }
+            }
+            _ => return Err(ParseError::InvalidEscape(self.src_pos(start))),
+        };
+
+        Ok(c)
+    }
+
+    /// Accumulates decimal digits, removing the `_` separators between them.
+    fn accumulate_digits(&mut self, start: usize) -> Result<String, ParseError> {
+        let digits = self.accumulate(|c| c.is_ascii_digit() || c == '_');
+        strip_separators(&digits).ok_or_else(|| ParseError::InvalidDigit(10, self.src_pos(start)))
+    }
+
+    /// Lexes a number literal, it ends at a delimiter, so the identifier characters right
+    /// after it, like in `12abc`, are part of the literal and make it invalid.
+    fn lex_number(&mut self) -> Result<TokenKind, ParseError> {
+        let start = self.index;
+        let result = self.lex_number_literal(start);
+        let rest = self.accumulate(is_identifier_char);
+
+        match result {
+            Ok(_) if !rest.is_empty() => Err(ParseError::InvalidDigit(10, self.src_pos(start))),
+            Ok(kind) => Ok(kind),
+            Err(mut error) => {
+                *error.src_pos_mut() = self.src_pos(start);
+                Err(error)
+            }
+        }
+    }
+
+    /// Lexes an integer, a ratio or a float literal, with an optional `-` sign. Integers
+    /// that don't fit in an `i64` are big integers, ratios are written like `-1/2`, and
+    /// floats can have a decimal part, an exponent part or both, like `1.5`, `1e10` or
+    /// `-1.5e-3`. Digits can be separated with `_`, like `1_000_000`.
+    fn lex_number_literal(&mut self, start: usize) -> Result<TokenKind, ParseError> {
+        let mut string = String::new();
+        if self.peek() == Some('-') {
+            string.push(self.bump().unwrap());
+        }
+
+        let radix = match self.string.get(self.index..self.index + 2) {
+            Some("0x") => Some(16),
+            Some("0o") => Some(8),
+            Some("0b") => Some(2),
+            _ => None,
+        };
+        if let Some(radix) = radix {
+            self.bump();
+            self.bump();
+            return self.lex_radix_integer(start, !string.is_empty(), radix);
+        }
+
+        string.push_str(&self.accumulate_digits(start)?);
+        let mut is_float = false;
+
+        if self.peek() == Some('.') {
+            string.push(self.bump().unwrap());
+            is_float = true;
+
+            let fraction = self.accumulate_digits(start)?;
+            if fraction.is_empty() {
+                return Err(ParseError::InvalidFloat(self.src_pos(start)));
+            }
+            string.push_str(&fraction);
+        }
+
+        if let Some('e' | 'E') = self.peek() {
+            string.push(self.bump().unwrap());
+            is_float = true;
+
+            if let Some('+' | '-') = self.peek() {
+                string.push(self.bump().unwrap());
+            }
+
+            let exponent = self.accumulate_digits(start)?;
+            if exponent.is_empty() {
+                return Err(ParseError::InvalidFloat(self.src_pos(start)));
+            }
+            string.push_str(&exponent);
+        }
+
+        // Floats that are too large are infinite, and they can't be read back.
+        if is_float {
+            return string
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(TokenKind::Float)
+                .ok_or_else(|| ParseError::InvalidFloat(self.src_pos(start)));
+        }
+
+        let numerator = BigInt::from_str_radix(&string, 10).unwrap();
+        let is_ratio = self.string[self.index..]
+            .strip_prefix('/')
+            .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()));
+        if !is_ratio {
+            return Ok(Number::from(numerator).into());
+        }
+
+        self.bump();
+        let denominator = BigInt::from_str_radix(&self.accumulate_digits(start)?, 10).unwrap();
+        Number::ratio(numerator, denominator)
+            .map(TokenKind::from)
+            .ok_or_else(|| ParseError::InvalidRatio(self.src_pos(start)))
+    }
+
+    /// Lexes the digits of an integer literal in base 16, 8 or 2, after its prefix, like
+    /// `0xFF`, `0o755` or `0b1010`. Like the decimal ones, they're big integers if they
+    /// don't fit in an `i64`.
+    fn lex_radix_integer(
+        &mut self,
+        start: usize,
+        negative: bool,
+        radix: u32,
+    ) -> Result<TokenKind, ParseError> {
+        // Every identifier character is consumed, so invalid digits are part of the
+        // literal instead of starting an identifier.
+        let digits = strip_separators(&self.accumulate(is_identifier_char))
+            .filter(|digits| !digits.is_empty() && digits.chars().all(|c| c.is_digit(radix)))
+            .ok_or_else(|| ParseError::InvalidDigit(radix, self.src_pos(start)))?;
+
+        let value = BigInt::from_str_radix(&digits, radix).unwrap();
+        let value = if negative { -&value } else { value };
+        Ok(Number::from(value).into())
+    }
+
+    /// Returns if the next characters are a `-` sign followed by a digit, otherwise the
+    /// `-` starts an identifier.
+    fn is_negative_number(&self) -> bool {
+        let mut chars = self.string[self.index..].chars();
+        chars.next() == Some('-') && chars.next().is_some_and(|c| c.is_ascii_digit())
+    }
+
+    /// Lexes a single character token.
+    fn single(&mut self, kind: TokenKind) -> Result<TokenKind, ParseError> {
+        self.bump();
+        Ok(kind)
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let start = self.index;
+
+        let kind = match self.peek()? {
+            c if c.is_whitespace() => {
+                self.accumulate(|c| c.is_whitespace());
+                Ok(TokenKind::Whitespace)
+            }
+            ';' => Ok(TokenKind::Comment(self.accumulate(|c| c != '\n'))),
+            '#' if self.string[self.index..].starts_with("#|") => self.lex_block_comment(),
+            '#' if self.string[self.index..].starts_with("#_") => {
+                self.bump();
+                self.single(TokenKind::DatumComment)
+            }
+            '"' => self.lex_string(),
+            '\\' => self.lex_char(),
+            ':' => {
+                self.bump();
+                Ok(TokenKind::Atom(self.accumulate(is_identifier_char)))
+            }
+            c if c.is_ascii_digit() => self.lex_number(),
+            '-' if self.is_negative_number() => self.lex_number(),
+            '(' => self.single(TokenKind::LParen),
+            ')' => self.single(TokenKind::RParen),
+            '[' => self.single(TokenKind::LBracket),
+            ']' => self.single(TokenKind::RBracket),
+            '{' => self.single(TokenKind::LBrace),
+            '}' => self.single(TokenKind::RBrace),
+            '\'' => self.single(TokenKind::Quote),
+            '`' => self.single(TokenKind::Quasiquote),
+            ',' => {
+                self.bump();
+                if self.peek() == Some('@') {
+                    self.single(TokenKind::UnquoteSplicing)
+                } else {
+                    Ok(TokenKind::Unquote)
+                }
+            }
+            _ => Ok(TokenKind::Identifier(self.accumulate(is_identifier_char))),
+        };
+
+        Some(Token {
+            kind: kind.unwrap_or_else(TokenKind::Error),
+            src_pos: self.src_pos(start),
+        })
+    }
+}
+
+impl From<Number> for TokenKind {
+    fn from(number: Number) -> Self {
+        match number {
+            Number::Int(value) => TokenKind::Int(value),
+            Number::BigInt(value) => TokenKind::BigInt(value),
+            Number::Ratio(value) => TokenKind::Ratio(value),
+            Number::Float(value) => TokenKind::Float(value),
+        }
+    }
+}
+
+/// Removes the `_` separators of the digits of a number, they're only valid between two
+/// digits, like in `1_000`.
+fn strip_separators(digits: &str) -> Option<String> {
+    let is_valid = !digits.starts_with('_') && !digits.ends_with('_') && !digits.contains("__");
+    is_valid.then(|| digits.replace('_', ""))
+}
+
+/// Tokenizes the whole string.
+pub fn tokenize(string: &str) -> Vec<Token> {
+    Lexer::new(string).collect()
+}
+
+/// Tests for the tokenizer.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(string: &str) -> Vec<TokenKind> {
+        tokenize(string)
+            .into_iter()
+            .map(|token| token.kind)
+            .collect()
+    }
+
+    #[test]
+    fn tokenizes_every_kind() {
+        use TokenKind::*;
+
+        assert_eq!(kinds("(f [:a 1 2.5] \"s\") ; c\n'x `y ,z ,@w {}"), [
+            LParen,
+            Identifier("f".to_string()),
+            Whitespace,
+            LBracket,
+            Atom("a".to_string()),
+            Whitespace,
+            Int(1),
+            Whitespace,
+            Float(2.5),
+            RBracket,
+            Whitespace,
+            String("s".to_string()),
+            RParen,
+            Whitespace,
+            Comment("; c".to_string()),
+            Whitespace,
+            Quote,
+            Identifier("x".to_string()),
+            Whitespace,
+            Quasiquote,
+            Identifier("y".to_string()),
+            Whitespace,
+            Unquote,
+            Identifier("z".to_string()),
+            Whitespace,
+            UnquoteSplicing,
+            Identifier("w".to_string()),
+            Whitespace,
+            LBrace,
+            RBrace,
+        ]);
+    }
+
+    #[test]
+    fn keeps_spans() {
+        let source = "(add \"é\" 12)";
+        let spans = tokenize(source)
+            .into_iter()
+            .map(|token| &source[token.src_pos.byte])
+            .collect::<Vec<_>>();
+
+        assert_eq!(spans, ["(", "add", " ", "\"é\"", " ", "12", ")"]);
+    }
+
+    #[test]
+    fn tokenizes_negative_numbers() {
+        assert_eq!(kinds("-12 -1.5 - -a"), [
+            TokenKind::Int(-12),
+            TokenKind::Whitespace,
+            TokenKind::Float(-1.5),
+            TokenKind::Whitespace,
+            TokenKind::Identifier("-".to_string()),
+            TokenKind::Whitespace,
+            TokenKind::Identifier("-a".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn tokenizes_radix_integers() {
+        assert_eq!(kinds("0xFF 0o755 0b1010 -0x10 1_000_000 1_0.5_0"), [
+            TokenKind::Int(255),
+            TokenKind::Whitespace,
+            TokenKind::Int(0o755),
+            TokenKind::Whitespace,
+            TokenKind::Int(10),
+            TokenKind::Whitespace,
+            TokenKind::Int(-16),
+            TokenKind::Whitespace,
+            TokenKind::Int(1_000_000),
+            TokenKind::Whitespace,
+            TokenKind::Float(10.5),
+        ]);
+        assert_eq!(kinds("-0x8000000000000000"), [TokenKind::Int(i64::MIN)]);
+        assert_eq!(kinds("0xFFFF_FFFF_FFFF_FFFF -0x8000000000000001"), [
+            TokenKind::BigInt(BigInt::from_str_radix("18446744073709551615", 10).unwrap()),
+            TokenKind::Whitespace,
+            TokenKind::BigInt(BigInt::from_str_radix("-9223372036854775809", 10).unwrap()),
+        ]);
+    }
+
+    #[test]
+    fn fails_on_invalid_digits() {
+        let tokens = tokenize("0b102 0xFG 0o 1__0 1_ 0x_F 1._5 12abc -1.5e3x 1/2/3");
+
+        assert!(matches!(
+            &tokens[0].kind,
+            TokenKind::Error(ParseError::InvalidDigit(2, SrcPos { byte, .. })) if *byte == (0..5)
+        ));
+        assert!(matches!(
+            tokens[2].kind,
+            TokenKind::Error(ParseError::InvalidDigit(16, _))
+        ));
+        assert!(matches!(
+            tokens[4].kind,
+            TokenKind::Error(ParseError::InvalidDigit(8, _))
+        ));
+        assert!(matches!(
+            tokens[10].kind,
+            TokenKind::Error(ParseError::InvalidDigit(16, _))
+        ));
+        for index in [6, 8, 12, 14, 16, 18] {
+            assert!(
+                matches!(
+                    tokens[index].kind,
+                    TokenKind::Error(ParseError::InvalidDigit(10, _))
+                ),
+                "{:?}",
+                tokens[index]
+            );
+        }
+        assert_eq!(tokens.len(), 19);
+        assert_eq!(tokens[14].src_pos.byte, 32..37);
+    }
+
+    #[test]
+    fn fails_on_infinite_floats() {
+        let tokens = tokenize("1e400 -1.5e309 1e308");
+
+        for index in [0, 2] {
+            assert!(matches!(
+                tokens[index].kind,
+                TokenKind::Error(ParseError::InvalidFloat(_))
+            ));
+        }
+        assert_eq!(tokens[2].src_pos.byte, 6..14);
+        assert_eq!(tokens[4].kind, TokenKind::Float(1e308));
+    }
+
+    #[test]
+    fn tokenizes_block_and_datum_comments() {
+        assert_eq!(kinds("#| a #| b |# c |#x #_y"), [
+            TokenKind::Comment("#| a #| b |# c |#".to_string()),
+            TokenKind::Identifier("x".to_string()),
+            TokenKind::Whitespace,
+            TokenKind::DatumComment,
+            TokenKind::Identifier("y".to_string()),
+        ]);
+        assert!(matches!(
+            &tokenize("#| a #| b |#")[0].kind,
+            TokenKind::Error(ParseError::UnterminatedComment(SrcPos { byte, .. })) if *byte == (0..12)
+        ));
+    }
+
+    #[test]
+    fn tokenizes_chars() {
+        assert_eq!(kinds("\\a \\newline \\u{1F600} \\( \\u"), [
+            TokenKind::Char('a'),
+            TokenKind::Whitespace,
+            TokenKind::Char('\n'),
+            TokenKind::Whitespace,
+            TokenKind::Char('\u{1F600}'),
+            TokenKind::Whitespace,
+            TokenKind::Char('('),
+            TokenKind::Whitespace,
+            TokenKind::Char('u'),
+        ]);
+
+        for source in ["\\ab", "\\u{110000}", "\\u{zz}", "\\u{41", "\\"] {
+            assert!(
+                matches!(
+                    &tokenize(source)[0],
+                    Token {
+                        kind: TokenKind::Error(ParseError::InvalidCharacter(_)),
+                        src_pos,
+                    } if src_pos.byte == (0..source.len())
+                ),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn tokenizes_big_integers_and_ratios() {
+        let big = |string: &str| BigInt::from_str_radix(string, 10).unwrap();
+        let ratio = |numerator: i64, denominator: i64| {
+            let Some(Number::Ratio(ratio)) =
+                Number::ratio(BigInt::from(numerator), BigInt::from(denominator))
+            else {
+                panic!("expected ratio");
+            };
+            ratio
+        };
+
+        assert_eq!(
+            kinds("99999999999999999999 -9223372036854775809 9223372036854775807"),
+            [
+                TokenKind::BigInt(big("99999999999999999999")),
+                TokenKind::Whitespace,
+                TokenKind::BigInt(big("-9223372036854775809")),
+                TokenKind::Whitespace,
+                TokenKind::Int(i64::MAX),
+            ]
+        );
+        assert_eq!(kinds("1/2 -6/4 4/2"), [
+            TokenKind::Ratio(ratio(1, 2)),
+            TokenKind::Whitespace,
+            TokenKind::Ratio(ratio(-3, 2)),
+            TokenKind::Whitespace,
+            TokenKind::Int(2),
+        ]);
+    }
+
+    #[test]
+    fn continues_after_invalid_tokens() {
+        let tokens = tokenize("\"\\q\" 1/0 a");
+
+        assert!(matches!(
+            tokens[0].kind,
+            TokenKind::Error(ParseError::InvalidEscape(_))
+        ));
+        assert_eq!(tokens[0].src_pos.byte, 0..4);
+        assert!(matches!(
+            tokens[2].kind,
+            TokenKind::Error(ParseError::InvalidRatio(_))
+        ));
+        assert_eq!(tokens[2].src_pos.byte, 5..8);
+        assert_eq!(tokens[4].kind, TokenKind::Identifier("a".to_string()));
+    }
+}
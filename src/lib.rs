@@ -1,7 +1,10 @@
 #![feature(box_patterns)]
 #![feature(try_trait_v2)]
 
-use std::fmt::Display;
+use std::{fmt::Display, sync::Arc};
+
+use special_form::SpecialForm;
+use symbol::Symbol;
 
 pub type Result<T, E = SemanticError> = std::result::Result<T, E>;
 
@@ -9,28 +12,77 @@ pub type Result<T, E = SemanticError> = std::result::Result<T, E>;
 /// a [crate::eval::Value].
 pub mod eval;
 
-/// Tokenization/lexing and parsing of Soft programming language, it will transform a string
-/// into [crate::Term].
+/// Functions implemented in Rust, that are defined in the root frame of every
+/// [crate::eval::Environment].
+pub mod intrinsics;
+
+/// Tokenization/lexing of Soft programming language, it will transform a string into a
+/// stream of [crate::lexer::Token].
+pub mod lexer;
+
+/// Arbitrary-precision integers and exact rationals, that complete the numeric tower
+/// with the machine integers and floats.
+pub mod number;
+
+/// Parsing of Soft programming language, it will transform the tokens of a string into
+/// [crate::Term].
 pub mod parser;
 
+/// Streaming reader of [crate::Term], it parses the top-level forms of any
+/// [std::io::BufRead] one at a time, without loading the whole input.
+pub mod reader;
+
+/// Width-aware pretty printer of [crate::Term], it's used by [std::fmt::Display].
+pub mod pretty;
+
+/// Registry of the special forms, like `fun*` or `def*`, that lower the lists whose head
+/// is their name into [crate::Expr].
+pub mod special_form;
+
+/// Interning of the names of identifiers, atoms and keywords into [crate::symbol::Symbol].
+pub mod symbol;
+
+/// Registry of the source files, it resolves a [crate::SrcPos] into file names, lines
+/// and columns.
+pub mod source;
+
 /// Term is a recursive data structure that represents a list of terms, an atom, an identifier,
 /// or an integer.
 ///
+/// The lists and strings are shared behind an [Arc], so cloning a term, like when it's
+/// lowered into an [Expr] or passed to a macro, doesn't copy the tree.
+///
 /// It's the first part of our Abstract-Syntax-Tree (AST).
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Term {
-    List(Vec<Term>),    // (a b c)
-    Vec(Vec<Term>),     // [a b c]
-    Atom(String),       // :bla
-    Identifier(String), // bla
-    Int(u64),           // 123
-    Float(u64, u64),    // 123.456
-    String(String),     // "some stuff"
+    List(Arc<[Term]>),      // (a b c)
+    Vec(Arc<[Term]>),       // [a b c]
+    Map(Arc<[Term]>),       // {:a 1 :b 2}, the keys are followed by their values
+    Atom(Symbol),           // :bla
+    Identifier(Symbol),     // bla
+    Int(i64),               // 123 or -123
+    Float(f64),             // 123.456
+    BigInt(number::BigInt), // 9223372036854775808
+    Ratio(number::Ratio),   // 1/2
+    String(Arc<str>),       // "some stuff"
+    Char(char),             // \a
+    Error,                  // invalid syntax, only produced by the parser in recovery mode
+    Trivia(Trivia),         // ; comment, only produced by the parser in lossless mode
+    Opaque(Arc<str>),       // #<fun f>, a value that can't be read, only produced by readback
     SrcPos(SrcPos, Box<Term>),
+
+    /// A literal with the text it was written with, like `0xFF` or `"\u{41}"`, it's only
+    /// produced by the parser in lossless mode, so literals are formatted as written.
+    Verbatim(Arc<str>, Box<Term>),
 }
 
-pub trait ExprKind: Sized {
-    fn try_new(term: Term) -> Result<Option<Expr>>;
+/// Comments and blank lines, that are kept in the tree by the parser in lossless mode so
+/// the source can be formatted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trivia {
+    Comment(String),         // ; comment in its own line
+    TrailingComment(String), // ; comment in the same line of the previous term
+    BlankLine,
 }
 
 // Abstract-Syntax-Tree (AST) for the Soft programming language. It's the representation
@@ -39,62 +91,91 @@ pub trait ExprKind: Sized {
 // It's the specialized phase of the language, where we have a concrete syntax and we
 // can start to evaluate the program.
 define_ast!(Expr, {
-    Fun,      // (fun* [a b] (+ a b))
-    List,     // [a b c] or (list a b c)
-    Apply,    // (a b c) or (apply a b c)
-    Def,      // (def* a 123)
-    Recur,    // (recur a)
-    DefMacro, // (defmacro* a (fun (a b) (+ a b))
-    Quote,    // '(fun* (a b) (+ a b))
-    Literal   // 123 | "bla" | :bla | bla
+    Fun,        // (fun* [a b] (+ a b))
+    List,       // [a b c] or (list a b c)
+    Map,        // {:a 1 :b 2}
+    Apply,      // (a b c) or (apply a b c)
+    Def,        // (def* a 123)
+    Recur,      // (recur a)
+    DefMacro,   // (defmacro* a (fun (a b) (+ a b))
+    Quote,      // '(fun* (a b) (+ a b))
+    Quasiquote, // `(a ,b ,@c)
+    Literal     // 123 | "bla" | :bla | bla
 });
 
 define_builtin!(DefMacro, "defmacro*", 2);
 define_builtin!(Def, "def*", 2);
 define_builtin!(Recur, "recur");
 define_builtin!(Fun, "fun*", 3);
-define_builtin!(Quote, "quote", 2);
-define_builtin!(Apply, "apply");
+define_builtin!(Quote, "quote", 1);
+define_builtin!(Quasiquote, "quasiquote", 1);
 
-/// Semantic errors that can occur during the specialization of an expression.
-#[derive(thiserror::Error, Debug, Clone)]
+/// Semantic errors that can occur during the specialization of an expression, they carry
+/// the position of the malformed form.
+#[derive(thiserror::Error, miette::Diagnostic, Debug, Clone, PartialEq)]
 pub enum SemanticError {
     #[error("invalid expression")]
-    InvalidExpression,
-
-    #[error("failed to match equivalent expression")]
-    FailedToMatch,
+    #[diagnostic(code(soft::semantic::invalid_expression))]
+    InvalidExpression(#[label("this expression is invalid")] SrcPos),
 
-    #[error("invalid list")]
-    InvalidList,
+    #[error("invalid list in {0}")]
+    #[diagnostic(code(soft::semantic::invalid_list))]
+    InvalidList(
+        &'static str,
+        #[label("this should be a list or a vector")] SrcPos,
+    ),
 
     #[error("invalid arguments")]
-    InvalidArguments,
-
-    #[error("missing function parameters")]
-    MissingParameters,
-
-    #[error("missing function body")]
-    MissingBody,
+    #[diagnostic(code(soft::semantic::invalid_arguments))]
+    InvalidArguments(#[label("this should be a list of arguments")] SrcPos),
+
+    #[error("missing parameters in {0}")]
+    #[diagnostic(
+        code(soft::semantic::missing_parameters),
+        help("functions are written like `(fun* name [a b] body)`")
+    )]
+    MissingParameters(
+        &'static str,
+        #[label("this function has no parameters")] SrcPos,
+    ),
+
+    #[error("missing body in {0}")]
+    #[diagnostic(
+        code(soft::semantic::missing_body),
+        help("functions are written like `(fun* name [a b] body)`")
+    )]
+    MissingBody(&'static str, #[label("this function has no body")] SrcPos),
 
     #[error("missing application head")]
-    MissingHead,
+    #[diagnostic(code(soft::semantic::missing_head))]
+    MissingHead(#[label("this application has no head")] SrcPos),
 
     #[error("expected string")]
-    ExpectedString,
-
-    #[error("expected vector with size {0}")]
-    ExpectedVectorWithSize(usize),
-
-    #[error("invalid quote expression")]
-    ExpectedQuoteExpression,
+    #[diagnostic(code(soft::semantic::expected_string))]
+    ExpectedString(#[label("this should be a string")] SrcPos),
+
+    #[error("{0} expects {1} arguments, got {2}")]
+    #[diagnostic(code(soft::semantic::incorrect_arity))]
+    IncorrectArity(
+        &'static str,
+        usize,
+        usize,
+        #[label("expected {1} arguments")] SrcPos,
+    ),
+
+    #[error("map literal has an odd number of forms")]
+    #[diagnostic(
+        code(soft::semantic::odd_map_entries),
+        help("maps are written with a value for every key, like `{{:a 1 :b 2}}`")
+    )]
+    OddMapEntries(#[label("this map has a key without value")] SrcPos),
 }
 
 /// Meta information about a term, or any other part of the AST.
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct SrcPos {
     pub byte: std::ops::Range<usize>,
-    pub file: String,
+    pub file: source::FileId,
 }
 
 impl SrcPos {
@@ -105,114 +186,142 @@ impl SrcPos {
     pub fn reset(&mut self) {
         self.byte.start = self.byte.end;
     }
+
+    /// Moves the position `offset` bytes forward, like when the text was read after
+    /// another one.
+    pub fn shift(&mut self, offset: usize) {
+        self.byte.start += offset;
+        self.byte.end += offset;
+    }
+
+    /// Resolves the start of the position into a file name, line and column.
+    pub fn location(&self) -> Option<source::Location> {
+        Some(self.file.source()?.location(self.byte.start))
+    }
+
+    /// Returns the source text that the position covers.
+    pub fn snippet(&self) -> Option<String> {
+        Some(self.file.source()?.snippet(self.byte.clone())?.to_string())
+    }
+}
+
+impl From<SrcPos> for miette::SourceSpan {
+    fn from(src_pos: SrcPos) -> Self {
+        src_pos.byte.into()
+    }
+}
+
+impl Display for SrcPos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location() {
+            Some(location) => write!(f, "{location}"),
+            None => write!(f, "{}..{}", self.byte.start, self.byte.end),
+        }
+    }
 }
 
 impl From<SemanticError> for Expr {
     fn from(value: SemanticError) -> Self {
         match value {
-            SemanticError::InvalidExpression => keyword!("error/invalid-expression"),
-            SemanticError::FailedToMatch => keyword!("error/failed-to-match"),
-            SemanticError::InvalidList => keyword!("error/invalid-list"),
-            SemanticError::InvalidArguments => keyword!("error/invalid-arguments"),
-            SemanticError::MissingParameters => keyword!("error/missing-parameters"),
-            SemanticError::MissingBody => keyword!("error/missing-body"),
-            SemanticError::MissingHead => keyword!("error/missing-head"),
-            SemanticError::ExpectedString => keyword!("error/expected-string"),
-            SemanticError::ExpectedVectorWithSize(size) => {
-                soft_vec![keyword!("error/expected-vector"), size]
+            SemanticError::InvalidExpression(_) => keyword!("error/invalid-expression"),
+            SemanticError::InvalidList(form, _) => {
+                soft_vec![keyword!("error/invalid-list"), form.to_string()]
+            }
+            SemanticError::InvalidArguments(_) => keyword!("error/invalid-arguments"),
+            SemanticError::MissingParameters(form, _) => {
+                soft_vec![keyword!("error/missing-parameters"), form.to_string()]
             }
-            SemanticError::ExpectedQuoteExpression => {
-                keyword!("error/expected-quote-expression")
+            SemanticError::MissingBody(form, _) => {
+                soft_vec![keyword!("error/missing-body"), form.to_string()]
             }
+            SemanticError::MissingHead(_) => keyword!("error/missing-head"),
+            SemanticError::ExpectedString(_) => keyword!("error/expected-string"),
+            SemanticError::IncorrectArity(form, expected, found, _) => {
+                soft_vec![
+                    keyword!("error/incorrect-arity"),
+                    form.to_string(),
+                    expected,
+                    found
+                ]
+            }
+            SemanticError::OddMapEntries(_) => keyword!("error/odd-map-entries"),
         }
     }
 }
 
 impl Term {
-    fn width(&self) -> usize {
-        match self {
-            Term::List(s) | Term::Vec(s) => {
-                let mut width = 2;
-                for t in s {
-                    width += t.width();
-                }
-                width
-            }
-            Term::Atom(s) => s.len(),
-            Term::Identifier(s) => s.len(),
-            Term::Int(n) => n.to_string().len(),
-            Term::Float(n, u) => n.to_string().len() + u.to_string().len() + 1,
-            Term::String(s) => s.len(),
-            Term::SrcPos(_, t) => t.width(),
+    pub fn transport(self, with: Term) -> Term {
+        if let Term::SrcPos(src_pos, _) = self {
+            Term::SrcPos(src_pos, with.into())
+        } else {
+            with
         }
     }
 
-    fn pretty_print(&self, f: &mut std::fmt::Formatter<'_>, indent: usize) -> std::fmt::Result {
+    pub fn at(&self, nth: usize) -> Option<Term> {
         match self {
-            Term::List(s) | Term::Vec(s) => {
-                if self.width() + indent > 80 {
-                    write!(f, "{:indent$}(", "", indent = indent)?;
-                    for t in s {
-                        write!(f, "{:indent$}", "", indent = indent + 2)?;
-                        t.pretty_print(f, indent + 2)?;
-                        writeln!(f)?;
-                    }
-                    write!(f, "{:indent$})", "", indent = indent)?;
-                }
+            Term::List(ls) => ls.get(nth).cloned(),
+            Term::SrcPos(_, t) => t.at(nth),
+            _ => None,
+        }
+    }
 
-                Ok(())
-            }
-            Term::Atom(s) => write!(f, ":{}", s),
-            Term::Identifier(s) => write!(f, "{}", s),
-            Term::Int(s) => write!(f, "{}", s),
-            Term::Float(u, n) => write!(f, "{}.{}", u, n),
-            Term::String(s) => write!(f, "\"{}\"", s),
-            Term::SrcPos(_, t) => t.pretty_print(f, indent),
+    pub fn split(&self) -> Option<(&Term, &[Term])> {
+        match self {
+            Term::List(ls) => ls.split_first(),
+            Term::SrcPos(_, t) => t.split(),
+            _ => None,
         }
     }
 
-    pub fn transport(self, with: Term) -> Term {
-        if let Term::SrcPos(src_pos, _) = self {
-            Term::SrcPos(src_pos, with.into())
-        } else {
-            with
+    /// Returns the name of the identifier.
+    pub fn identifier(&self) -> Option<Symbol> {
+        match self {
+            Term::Identifier(x) => Some(*x),
+            Term::SrcPos(_, t) => t.identifier(),
+            _ => None,
         }
     }
 
-    pub fn at(&self, nth: usize) -> Option<Term> {
-        if let Term::List(ls) = self {
-            ls.get(nth).cloned()
-        } else {
-            None
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        match self {
+            Term::Identifier(x) => x.as_str() == keyword,
+            Term::SrcPos(_, t) => t.is_keyword(keyword),
+            _ => false,
         }
     }
 
-    pub fn split(&self) -> Option<(Term, Vec<Term>)> {
-        if let Term::List(ls) = self {
-            let (first, rest) = ls.split_first()?;
-            Some((first.clone(), rest.to_vec()))
-        } else {
-            None
+    pub fn spine(&self) -> Option<&[Term]> {
+        match self {
+            Term::List(ls) | Term::Vec(ls) => Some(ls),
+            Term::SrcPos(_, t) => t.spine(),
+            _ => None,
         }
     }
 
-    pub fn is_keyword(&self, keyword: &str) -> bool {
-        matches!(self, Term::Identifier(x) if x == keyword)
+    /// Returns the position of the term, or the default position if it has none.
+    pub fn src_pos(&self) -> SrcPos {
+        match self {
+            Term::SrcPos(src_pos, _) => src_pos.clone(),
+            _ => SrcPos::default(),
+        }
     }
 
-    pub fn spine(&self) -> Option<Vec<Term>> {
-        if let Term::List(ls) = self.clone() {
-            Some(ls.clone())
-        } else {
-            None
+    pub fn is_blank_line(&self) -> bool {
+        match self {
+            Term::Trivia(Trivia::BlankLine) => true,
+            Term::SrcPos(_, t) => t.is_blank_line(),
+            _ => false,
         }
     }
 
     /// Removes meta information from a term.
     pub fn unbox(self) -> Term {
         match self {
-            Term::SrcPos(_, t) => t.unbox(),
-            Term::List(x) => Term::List(x.into_iter().map(|t| t.unbox()).collect()),
+            Term::SrcPos(_, t) | Term::Verbatim(_, t) => t.unbox(),
+            Term::List(x) => Term::List(x.iter().cloned().map(Term::unbox).collect()),
+            Term::Vec(x) => Term::Vec(x.iter().cloned().map(Term::unbox).collect()),
+            Term::Map(x) => Term::Map(x.iter().cloned().map(Term::unbox).collect()),
             t => t,
         }
     }
@@ -227,21 +336,30 @@ pub mod fun {
         pub fn name(&self) -> Result<Expr> {
             self.0
                 .at(1)
-                .ok_or(SemanticError::InvalidExpression)?
+                .ok_or_else(|| SemanticError::InvalidExpression(self.src_pos()))?
                 .try_into()
         }
 
         /// Returns a list of parameters that are in the spine of the function.
         pub fn parameters(&self) -> Result<List> {
-            self.0
-                .at(2)
-                .map(List)
-                .ok_or(SemanticError::MissingParameters)
+            let parameters = self.0.at(2).ok_or_else(|| {
+                SemanticError::MissingParameters(Self::SPECIAL_FORM.name, self.src_pos())
+            })?;
+            match parameters.spine() {
+                Some(_) => Ok(List(parameters)),
+                None => Err(SemanticError::InvalidList(
+                    Self::SPECIAL_FORM.name,
+                    parameters.src_pos(),
+                )),
+            }
         }
 
         /// Returns the body of the function.
         pub fn body(&self) -> Result<Expr> {
-            self.0.at(3).ok_or(SemanticError::MissingBody)?.try_into()
+            self.0
+                .at(3)
+                .ok_or_else(|| SemanticError::MissingBody(Self::SPECIAL_FORM.name, self.src_pos()))?
+                .try_into()
         }
     }
 }
@@ -252,55 +370,89 @@ pub mod list {
 
     use super::*;
 
-    impl ExprKind for List {
-        fn try_new(term: Term) -> Result<Option<Expr>> {
-            println!("podim");
-            if let Term::Vec(ref vec) | Term::SrcPos(_, box Term::Vec(ref vec)) = term {
-                println!("pao de acucar");
-                let items = vec.clone().into();
-                return Ok(Some(List(term.transport(items)).into()));
-            }
-
-            let (head, tail) = term.split().ok_or(InvalidExpression)?;
-            if head.is_keyword("list") {
-                let tail = assert_length(tail, 1)?;
-                Ok(Some(List(term.transport(tail.into())).into()))
-            } else {
-                Ok(None)
-            }
-        }
-    }
-
     impl List {
+        /// The `(list a b c)` special form.
+        pub const SPECIAL_FORM: SpecialForm = SpecialForm {
+            name: "list",
+            arity: None,
+            lower: |term| {
+                let (_, tail) = term
+                    .split()
+                    .ok_or_else(|| InvalidExpression(term.src_pos()))?;
+                let items = Term::List(tail.into());
+                Ok(List(term.transport(items)).into())
+            },
+        };
+
         /// Returns a list of expressions that are in the spine of the list.
         pub fn elements(&self) -> Result<Vec<Expr>> {
             self.0
                 .spine()
-                .ok_or(SemanticError::InvalidList)?
-                .into_iter()
+                .ok_or_else(|| SemanticError::InvalidList(Self::SPECIAL_FORM.name, self.src_pos()))?
+                .iter()
+                .cloned()
                 .map(Expr::try_from)
                 .collect()
         }
     }
 }
 
+/// Map expression construct, it's a map literal of keys and values.
+pub mod map {
+    use super::*;
+
+    impl Map {
+        /// Returns the keys and values of the map, in the order they were written.
+        pub fn entries(&self) -> Result<Vec<(Expr, Expr)>> {
+            let (Term::Map(terms) | Term::SrcPos(_, box Term::Map(terms))) = &self.0 else {
+                return Err(SemanticError::InvalidExpression(self.src_pos()));
+            };
+            if !terms.len().is_multiple_of(2) {
+                return Err(SemanticError::OddMapEntries(self.src_pos()));
+            }
+
+            terms
+                .chunks(2)
+                .map(|entry| Ok((entry[0].clone().try_into()?, entry[1].clone().try_into()?)))
+                .collect()
+        }
+    }
+}
+
 /// Apply expression construct, it's a function application.
 pub mod apply {
     use super::*;
 
     impl Apply {
+        /// The `(apply f a b)` special form, the other lists are applications too.
+        pub const SPECIAL_FORM: SpecialForm = SpecialForm {
+            name: "apply",
+            arity: None,
+            lower: |term| {
+                let (_, tail) = term
+                    .split()
+                    .ok_or_else(|| SemanticError::InvalidExpression(term.src_pos()))?;
+                let tail = Term::List(tail.into());
+                Ok(Apply(term.transport(tail)).into())
+            },
+        };
+
         /// Returns the callee of the application.
         pub fn callee(&self) -> Result<Expr> {
-            self.0.at(0).ok_or(SemanticError::MissingHead)?.try_into()
+            self.0
+                .at(0)
+                .ok_or_else(|| SemanticError::MissingHead(self.src_pos()))?
+                .try_into()
         }
 
         /// Returns a list of arguments that are in the spine of the application.
         pub fn spine(&self) -> Result<Vec<Expr>> {
             self.0
                 .spine()
-                .ok_or(SemanticError::InvalidArguments)?
-                .into_iter()
+                .ok_or_else(|| SemanticError::InvalidArguments(self.src_pos()))?
+                .iter()
                 .skip(1) // Skip the head of the application.
+                .cloned()
                 .map(Expr::try_from)
                 .collect()
         }
@@ -316,9 +468,10 @@ pub mod recur {
         pub fn spine(&self) -> Result<Vec<Expr>> {
             self.0
                 .spine()
-                .ok_or(SemanticError::InvalidArguments)?
-                .into_iter()
+                .ok_or_else(|| SemanticError::InvalidArguments(self.src_pos()))?
+                .iter()
                 .skip(1) // Skip the head of the application.
+                .cloned()
                 .map(Expr::try_from)
                 .collect()
         }
@@ -334,7 +487,7 @@ pub mod def {
         pub fn name(&self) -> Result<Expr> {
             self.0
                 .at(1)
-                .ok_or(SemanticError::InvalidExpression)?
+                .ok_or_else(|| SemanticError::InvalidExpression(self.src_pos()))?
                 .try_into()
         }
 
@@ -342,7 +495,7 @@ pub mod def {
         pub fn value(&self) -> Result<Expr> {
             self.0
                 .at(2)
-                .ok_or(SemanticError::InvalidExpression)?
+                .ok_or_else(|| SemanticError::InvalidExpression(self.src_pos()))?
                 .try_into()
         }
     }
@@ -357,7 +510,7 @@ pub mod defmacro {
         pub fn name(&self) -> Result<Expr> {
             self.0
                 .at(1)
-                .ok_or(SemanticError::InvalidExpression)?
+                .ok_or_else(|| SemanticError::InvalidExpression(self.src_pos()))?
                 .try_into()
         }
 
@@ -365,7 +518,7 @@ pub mod defmacro {
         pub fn value(&self) -> Result<Expr> {
             self.0
                 .at(2)
-                .ok_or(SemanticError::InvalidExpression)?
+                .ok_or_else(|| SemanticError::InvalidExpression(self.src_pos()))?
                 .try_into()
         }
     }
@@ -380,116 +533,167 @@ pub mod quote {
         pub fn expr(&self) -> Result<Expr> {
             self.0
                 .at(1)
-                .ok_or(SemanticError::InvalidExpression)?
+                .ok_or_else(|| SemanticError::InvalidExpression(self.src_pos()))?
                 .try_into()
         }
     }
 }
 
-/// Literal expression construct, it's a literal value.
-pub mod literal {
+/// Quasiquote expression construct, it's a quoted template that can contain `unquote` and
+/// `unquote-splicing` holes.
+pub mod quasiquote {
     use super::*;
 
-    impl ExprKind for Literal {
-        fn try_new(term: Term) -> Result<Option<Expr>> {
-            Ok(Some(Expr::Literal(Literal(term))))
+    impl Quasiquote {
+        /// Returns the template of the quasiquote.
+        pub fn template(&self) -> Result<Term> {
+            self.0
+                .at(1)
+                .ok_or_else(|| SemanticError::InvalidExpression(self.src_pos()))
         }
     }
+}
+
+/// Literal expression construct, it's a literal value.
+pub mod literal {
+    use super::*;
 
     impl From<String> for Expr {
         fn from(value: String) -> Self {
-            Expr::Literal(Literal(Term::String(value)))
+            Expr::Literal(Literal(Term::String(value.into())))
         }
     }
 
-    impl From<u64> for Expr {
-        fn from(value: u64) -> Self {
+    impl From<i64> for Expr {
+        fn from(value: i64) -> Self {
             Expr::Literal(Literal(Term::Int(value)))
         }
     }
 
     impl From<usize> for Expr {
         fn from(value: usize) -> Self {
-            Expr::Literal(Literal(Term::Int(value as u64)))
+            Expr::Literal(Literal(Term::Int(value as i64)))
+        }
+    }
+
+    impl From<f64> for Expr {
+        fn from(value: f64) -> Self {
+            Expr::Literal(Literal(Term::Float(value)))
         }
     }
 
     impl Expr {
         pub fn new_keyword(keyword: &str) -> Self {
-            Expr::Literal(Literal(Term::Atom(keyword.to_string())))
+            Expr::Literal(Literal(Term::Atom(keyword.into())))
         }
 
         /// Expects a string literal and returns it's value.
         pub fn string(&self) -> Result<String> {
             match self {
-                Expr::Literal(Literal(Term::String(string))) => Ok(string.clone()),
-                _ => Err(SemanticError::ExpectedString),
+                Expr::Literal(Literal(Term::String(string))) => Ok(string.to_string()),
+                _ => Err(SemanticError::ExpectedString(self.src_pos())),
             }
         }
     }
 }
 
-fn assert_length(list: Vec<Term>, length: usize) -> Result<Vec<Term>, SemanticError> {
-    if list.len() != length {
-        Err(SemanticError::ExpectedVectorWithSize(length))
-    } else {
-        Ok(list)
+/// Escapes a string so it can be read again as a string literal, including the quotes.
+pub fn escape_string(string: &str) -> String {
+    let mut escaped = String::from('"');
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Escapes a character so it can be read again as a character literal, including the `\\`.
+pub fn escape_char(c: char) -> String {
+    match c {
+        '\n' => "\\newline".to_string(),
+        ' ' => "\\space".to_string(),
+        '\t' => "\\tab".to_string(),
+        '\r' => "\\return".to_string(),
+        c if c.is_control() || c.is_whitespace() => format!("\\u{{{:x}}}", c as u32),
+        c => format!("\\{c}"),
     }
 }
 
 impl TryFrom<Term> for Expr {
     type Error = SemanticError;
 
+    /// Lowers the term into an expression, the lists whose head is registered in the
+    /// [special_form] registry are lowered by their special form, and the other ones are
+    /// applications.
     fn try_from(value: Term) -> Result<Self, Self::Error> {
-        try_expr!(DefMacro, value);
-        try_expr!(Def, value);
-        try_expr!(Recur, value);
-        try_expr!(Fun, value);
-        try_expr!(Quote, value);
-        try_expr!(Apply, value);
-        try_expr!(List, value);
-        try_expr!(Literal, value);
-        Err(SemanticError::FailedToMatch)
+        match value {
+            Term::Vec(ref vec) | Term::SrcPos(_, box Term::Vec(ref vec)) => {
+                let items = Term::List(vec.clone());
+                return Ok(List(value.transport(items)).into());
+            }
+            Term::Map(_) | Term::SrcPos(_, box Term::Map(_)) => return Ok(Map(value).into()),
+            _ => {}
+        }
+
+        let Some((head, _)) = value.split() else {
+            return Ok(Literal(value).into());
+        };
+
+        match head.identifier().and_then(special_form::get) {
+            Some(special_form) => special_form.apply(value),
+            None => Ok(Apply(value).into()),
+        }
     }
 }
 
 impl Display for Term {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.pretty_print(f, 0)
+        write!(f, "{}", self.pretty(&pretty::Style::default()))
     }
 }
 
 impl From<String> for Term {
     fn from(s: String) -> Self {
-        Term::String(s)
+        Term::String(s.into())
+    }
+}
+
+impl From<i64> for Term {
+    fn from(n: i64) -> Self {
+        Term::Int(n)
     }
 }
 
 impl From<usize> for Term {
     fn from(n: usize) -> Self {
-        Term::Int(n as u64)
+        Term::Int(n as i64)
     }
 }
 
-impl From<Vec<Term>> for Term {
-    fn from(terms: Vec<Term>) -> Self {
-        Term::List(terms)
+impl From<f64> for Term {
+    fn from(n: f64) -> Self {
+        Term::Float(n)
     }
 }
 
-#[macro_export]
-macro_rules! try_expr {
-    ($name:ident, $value:expr) => {
-        if let Ok(Some(value)) = $name::try_new($value.clone()) {
-            return Ok(value);
-        }
-    };
+impl From<Vec<Term>> for Term {
+    fn from(terms: Vec<Term>) -> Self {
+        Term::List(terms.into())
+    }
 }
 
 #[macro_export]
 macro_rules! soft_vec {
     ($($expr:expr),*) => {
-        $crate::Expr::List($crate::List($crate::Term::Vec(vec![$($expr.into()),*])))
+        $crate::Expr::List($crate::List($crate::Term::Vec([$($expr.into()),*].into())))
     };
 }
 
@@ -502,35 +706,23 @@ macro_rules! keyword {
 
 #[macro_export]
 macro_rules! define_builtin {
-    ($name:ident, $keyword:expr, $length:expr) => {
-        impl $crate::ExprKind for $name {
-            fn try_new(term: $crate::Term) -> $crate::Result<Option<$crate::Expr>> {
-                let (head, tail) = term
-                    .split()
-                    .ok_or($crate::SemanticError::InvalidExpression)?;
-                if head.is_keyword($keyword) {
-                    let tail = $crate::assert_length(tail, $length)?;
-                    Ok(Some($name(term.transport(tail.into())).into()))
-                } else {
-                    Ok(None)
-                }
-            }
-        }
-    };
-    ($name:ident, $keyword:expr) => {
-        impl $crate::ExprKind for $name {
-            fn try_new(term: $crate::Term) -> $crate::Result<Option<$crate::Expr>> {
-                let Some((head, tail)) = term.split() else {
-                    return Ok(None);
+    (@form $name:ident, $keyword:literal, $arity:expr) => {
+        impl $name {
+            #[doc = concat!("The `(", $keyword, " ...)` special form.")]
+            pub const SPECIAL_FORM: $crate::special_form::SpecialForm =
+                $crate::special_form::SpecialForm {
+                    name: $keyword,
+                    arity: $arity,
+                    lower: |term| Ok($name(term).into()),
                 };
-                if head.is_keyword($keyword) {
-                    Ok(Some($name(term.transport(tail.into())).into()))
-                } else {
-                    Ok(Some($crate::Expr::from($name(term))))
-                }
-            }
         }
     };
+    ($name:ident, $keyword:literal, $length:expr) => {
+        $crate::define_builtin!(@form $name, $keyword, Some($length));
+    };
+    ($name:ident, $keyword:literal) => {
+        $crate::define_builtin!(@form $name, $keyword, None);
+    };
 }
 
 #[macro_export]
@@ -545,6 +737,17 @@ macro_rules! define_ast {
             ),+
         }
 
+        impl $name {
+            /// Returns the position of the term of the expression.
+            pub fn src_pos(&self) -> $crate::SrcPos {
+                match self {
+                    $(
+                        $name::$variant(value) => value.src_pos(),
+                    )+
+                }
+            }
+        }
+
         impl From<$name> for $crate::Term {
             fn from(value: $name) -> Self {
                 match value {
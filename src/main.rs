@@ -1,18 +1,26 @@
 use std::path::PathBuf;
 
-use clap::Parser;
-use miette::IntoDiagnostic;
+use clap::{Parser, Subcommand};
+use miette::{Diagnostic, IntoDiagnostic, NamedSource};
 use rustyline::{
     error::ReadlineError, validate::MatchingBracketValidator, Completer, Editor, Helper,
     Highlighter, Hinter, Validator,
 };
-use soft::{eval::Environment, Expr, Term};
+use soft::{
+    eval::{Environment, RuntimeError, Value},
+    parser::Parser as SexprParser,
+    pretty::Style,
+    reader::Reader,
+    source::{self, FileId},
+    Expr, Term,
+};
 
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Loads a file to use as input.
+    /// Loads a file to use as input, `-` reads the forms from the standard input as they
+    /// arrive.
     #[arg(short, long)]
     load: Option<String>,
 
@@ -22,6 +30,22 @@ struct Args {
     /// Starts a repl session.
     #[arg(short, long)]
     repl: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Formats `.soft` files in place.
+    Fmt {
+        /// The files to format.
+        files: Vec<PathBuf>,
+
+        /// Checks if the files are formatted instead of changing them.
+        #[arg(long)]
+        check: bool,
+    },
 }
 
 #[derive(Completer, Helper, Highlighter, Hinter, Validator)]
@@ -36,7 +60,17 @@ fn main() -> miette::Result<()> {
 
     // Parse the command line arguments.
     let args = Args::parse();
+    if let Some(Command::Fmt { files, check }) = args.command {
+        return fmt(files, check);
+    }
+
     let environment = Environment::default();
+    if let Some("-") = args.load.as_deref() {
+        load_stdin(&environment);
+    } else if let Some(path) = args.load {
+        let content = std::fs::read_to_string(&path).into_diagnostic()?;
+        load(path, content, &environment);
+    }
     if let Some(expr) = args.exe {
         exec(expr, &environment);
     }
@@ -54,17 +88,121 @@ fn get_history_path() -> Option<PathBuf> {
 }
 
 pub fn exec(content: String, environment: &Environment) {
-    let value = soft::parser::parse_sexpr(&content)
-        .and_then(|sexpr| Expr::try_from(sexpr).map_err(|error| error.into()))
-        .and_then(|expr| expr.expand(environment))
-        .and_then(|expr| expr.eval(environment).eval_into_result());
+    eval_file(source::add_file("<input>", content), environment)
+}
+
+/// Evaluates the single form of a registered file, and prints its value.
+fn eval_file(file: FileId, environment: &Environment) {
+    let text = file.source().unwrap().text.clone();
+    let sexpr = match SexprParser::with_file(file, &text).parse() {
+        Ok(sexpr) => sexpr,
+        Err(error) => return report(error, file),
+    };
 
-    match value {
+    match eval_term(sexpr, environment) {
         Ok(value) => println!("{}", value.readback()),
-        Err(error) => eprintln!("{}", Term::from(error)),
+        Err(error) => report(error, file),
+    }
+}
+
+/// Lowers, expands and evaluates a top-level form. The errors raised without a position,
+/// like the undefined keywords, point at the whole form.
+fn eval_term(term: Term, environment: &Environment) -> Result<Value, RuntimeError> {
+    let src_pos = term.src_pos();
+    Expr::try_from(term)
+        .map_err(RuntimeError::from)
+        .and_then(|expr| expr.expand(environment))
+        .and_then(|value| value.eval(environment).eval_into_result())
+        .map_err(|error| error.with_src_pos(src_pos))
+}
+
+/// Evaluates every top-level form of a file, stopping at the first error.
+pub fn load(path: String, content: String, environment: &Environment) {
+    let file = source::add_file(path, content);
+    let text = file.source().unwrap().text.clone();
+    let terms = match SexprParser::with_file(file, &text).parse_program() {
+        Ok(terms) => terms,
+        Err(error) => return report(error, file),
+    };
+
+    for term in terms {
+        if let Err(error) = eval_term(term, environment) {
+            return report(error, file);
+        }
     }
 }
 
+/// Evaluates every top-level form of the standard input as soon as it's read, stopping
+/// at the first error.
+pub fn load_stdin(environment: &Environment) {
+    let file = source::add_file("<stdin>", "");
+    for term in Reader::with_file(file, std::io::stdin().lock()) {
+        let term = match term {
+            Ok(term) => term,
+            Err(error) => return report(error, file),
+        };
+
+        if let Err(error) = eval_term(term, environment) {
+            return report(error, file);
+        }
+    }
+}
+
+/// Formats the files in place, or only checks if they're formatted, failing if any of
+/// them isn't or can't be parsed.
+fn fmt(files: Vec<PathBuf>, check: bool) -> miette::Result<()> {
+    let style = Style::default();
+    let mut unformatted = 0;
+    let mut failed = 0;
+
+    for path in files {
+        let content = std::fs::read_to_string(&path).into_diagnostic()?;
+        let file = source::add_file(path.display().to_string(), content);
+        let text = file.source().unwrap().text.clone();
+        let terms = match SexprParser::with_file(file, &text).parse_program_lossless() {
+            Ok(terms) => terms,
+            Err(error) => {
+                report(error, file);
+                failed += 1;
+                continue;
+            }
+        };
+
+        let formatted = style.format_program(&terms);
+        if formatted == *text {
+            continue;
+        }
+
+        if check {
+            eprintln!("{} is not formatted", path.display());
+            unformatted += 1;
+        } else {
+            std::fs::write(&path, formatted).into_diagnostic()?;
+        }
+    }
+
+    match (failed, unformatted) {
+        (0, 0) => Ok(()),
+        (0, unformatted) => miette::bail!("{unformatted} file(s) are not formatted"),
+        (failed, 0) => miette::bail!("{failed} file(s) could not be parsed"),
+        (failed, unformatted) => miette::bail!(
+            "{failed} file(s) could not be parsed and {unformatted} file(s) are not formatted"
+        ),
+    }
+}
+
+/// Renders an error with the graphical reporter, with the source code of the file so the
+/// labels can point at it.
+fn report(error: impl Diagnostic + Send + Sync + 'static, file: FileId) {
+    let mut report = miette::Report::new(error);
+    if let Some(source) = file.source() {
+        let source_code = NamedSource::new(&source.name, source.text.to_string());
+        report = report.with_source_code(source_code);
+    }
+
+    eprintln!("{report:?}");
+}
+
 pub fn repl(environment: &Environment) {
     let mut rl = Editor::new().expect("cannot create repl");
     let path = get_history_path();
@@ -80,11 +218,16 @@ pub fn repl(environment: &Environment) {
         }
     }
 
+    // Every line replaces the text of the same file, as the diagnostics of a line are
+    // reported before the next one is read.
+    let file = source::add_file("<repl>", "");
+
     loop {
         match rl.readline("> ") {
             Ok(line) => {
                 rl.add_history_entry(line.as_str()).unwrap();
-                exec(line, environment)
+                source::replace_file(file, line);
+                eval_file(file, environment)
             }
             Err(ReadlineError::Interrupted) => {
                 println!("Interrupted");
@@ -0,0 +1,694 @@
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    ops::{Add, Mul, Neg, Sub},
+};
+
+use crate::{
+    eval::{EvalError, Value},
+    SrcPos,
+};
+
+/// An arbitrary-precision integer, stored as its sign and its magnitude in base 2^32
+/// digits, from the least significant one. The magnitude never has leading zeros, and
+/// zero is never negative.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+/// An exact rational, always in lowest terms and with a denominator greater than one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Ratio {
+    numerator: BigInt,
+    denominator: BigInt,
+}
+
+/// A number of the numeric tower. Exact numbers are always normalized into their
+/// smallest representation, so a [BigInt] never fits in an `i64`, and a [Ratio] is never
+/// an integer.
+#[derive(Debug, Clone)]
+pub enum Number {
+    Int(i64),
+    BigInt(BigInt),
+    Ratio(Ratio),
+    Float(f64),
+}
+
+/// Removes the leading zeros of a magnitude.
+fn trim(mut magnitude: Vec<u32>) -> Vec<u32> {
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+    magnitude
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for index in 0..a.len().max(b.len()) {
+        let sum = *a.get(index).unwrap_or(&0) as u64 + *b.get(index).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    result.push(carry as u32);
+    trim(result)
+}
+
+/// Subtracts the magnitudes, `a` should be greater or equal than `b`.
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (index, &digit) in a.iter().enumerate() {
+        let difference = digit as i64 - *b.get(index).unwrap_or(&0) as i64 - borrow;
+        result.push(difference.rem_euclid(1 << 32) as u32);
+        borrow = (difference < 0) as i64;
+    }
+    trim(result)
+}
+
+fn mul_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = vec![0; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0;
+        for (j, &y) in b.iter().enumerate() {
+            let product = result[i + j] as u64 + x as u64 * y as u64 + carry;
+            result[i + j] = product as u32;
+            carry = product >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    trim(result)
+}
+
+/// Divides the magnitude by a single digit, returning the quotient and the remainder.
+fn div_rem_digit(a: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0; a.len()];
+    let mut remainder = 0u64;
+    for (index, &digit) in a.iter().enumerate().rev() {
+        let dividend = (remainder << 32) | digit as u64;
+        quotient[index] = (dividend / divisor as u64) as u32;
+        remainder = dividend % divisor as u64;
+    }
+    (trim(quotient), remainder as u32)
+}
+
+/// Divides the magnitudes with binary long division, returning the quotient and the
+/// remainder. The divisor shouldn't be zero.
+fn div_rem_magnitude(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if let [divisor] = b {
+        let (quotient, remainder) = div_rem_digit(a, *divisor);
+        return (quotient, trim(vec![remainder]));
+    }
+
+    let mut quotient = vec![0; a.len()];
+    let mut remainder = Vec::new();
+    for bit in (0..a.len() * 32).rev() {
+        // Shifts the remainder to the left, bringing down the next bit of the dividend.
+        let mut carry = (a[bit / 32] >> (bit % 32)) & 1;
+        for digit in remainder.iter_mut() {
+            let next = *digit >> 31;
+            *digit = (*digit << 1) | carry;
+            carry = next;
+        }
+        if carry != 0 {
+            remainder.push(carry);
+        }
+
+        if cmp_magnitude(&remainder, b) != Ordering::Less {
+            remainder = sub_magnitude(&remainder, b);
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (trim(quotient), remainder)
+}
+
+impl BigInt {
+    fn new(negative: bool, magnitude: Vec<u32>) -> Self {
+        let magnitude = trim(magnitude);
+        Self {
+            negative: negative && !magnitude.is_empty(),
+            magnitude,
+        }
+    }
+
+    /// Parses the digits of an integer in the radix, with an optional `-` sign. Returns
+    /// `None` if there are no digits, or if any of them is invalid.
+    pub fn from_str_radix(string: &str, radix: u32) -> Option<BigInt> {
+        let (negative, digits) = match string.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, string),
+        };
+        if digits.is_empty() {
+            return None;
+        }
+
+        let mut magnitude = Vec::new();
+        for c in digits.chars() {
+            let mut carry = c.to_digit(radix)? as u64;
+            for digit in magnitude.iter_mut() {
+                let value = *digit as u64 * radix as u64 + carry;
+                *digit = value as u32;
+                carry = value >> 32;
+            }
+            if carry != 0 {
+                magnitude.push(carry as u32);
+            }
+        }
+
+        Some(BigInt::new(negative, magnitude))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt::new(false, self.magnitude.clone())
+    }
+
+    /// Returns the integer if it fits in an `i64`.
+    pub fn to_i64(&self) -> Option<i64> {
+        let magnitude = match self.magnitude.as_slice() {
+            [] => 0,
+            [low] => *low as i128,
+            [low, high] => ((*high as i128) << 32) | *low as i128,
+            _ => return None,
+        };
+
+        i64::try_from(if self.negative { -magnitude } else { magnitude }).ok()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self
+            .magnitude
+            .iter()
+            .rev()
+            .fold(0.0, |value, &digit| value * 4294967296.0 + digit as f64);
+
+        if self.negative {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    /// Divides rounding towards zero, returning the quotient and the remainder, that has
+    /// the sign of the dividend. Returns `None` if the divisor is zero.
+    pub fn div_rem(&self, divisor: &BigInt) -> Option<(BigInt, BigInt)> {
+        if divisor.is_zero() {
+            return None;
+        }
+
+        let (quotient, remainder) = div_rem_magnitude(&self.magnitude, &divisor.magnitude);
+        Some((
+            BigInt::new(self.negative != divisor.negative, quotient),
+            BigInt::new(self.negative, remainder),
+        ))
+    }
+
+    /// The greatest common divisor, that is always positive.
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let mut a = self.abs();
+        let mut b = other.abs();
+        while let Some((_, remainder)) = a.div_rem(&b) {
+            a = b;
+            b = remainder;
+        }
+        a
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> Self {
+        let magnitude = value.unsigned_abs();
+        BigInt::new(value < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.magnitude.clone())
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(
+                self.negative,
+                add_magnitude(&self.magnitude, &other.magnitude),
+            );
+        }
+
+        // The signs are different, so the smaller magnitude is subtracted from the greater.
+        match cmp_magnitude(&self.magnitude, &other.magnitude) {
+            Ordering::Less => BigInt::new(
+                other.negative,
+                sub_magnitude(&other.magnitude, &self.magnitude),
+            ),
+            _ => BigInt::new(
+                self.negative,
+                sub_magnitude(&self.magnitude, &other.magnitude),
+            ),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::new(
+            self.negative != other.negative,
+            mul_magnitude(&self.magnitude, &other.magnitude),
+        )
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_magnitude(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        // Splits the magnitude into chunks of 9 decimal digits, from the least significant.
+        let mut chunks = Vec::new();
+        let mut magnitude = self.magnitude.clone();
+        while !magnitude.is_empty() {
+            let (quotient, remainder) = div_rem_digit(&magnitude, 1_000_000_000);
+            chunks.push(remainder);
+            magnitude = quotient;
+        }
+
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.into_iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        for chunk in chunks {
+            write!(f, "{chunk:09}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The numerator and the denominator of an exact number.
+type Fraction = (BigInt, BigInt);
+
+impl Number {
+    /// Creates the number `numerator / denominator` in lowest terms, it's only a ratio if
+    /// it isn't an integer. Returns `None` if the denominator is zero.
+    pub fn ratio(numerator: BigInt, denominator: BigInt) -> Option<Number> {
+        if denominator.is_zero() {
+            return None;
+        }
+
+        let gcd = numerator.gcd(&denominator);
+        let (mut numerator, _) = numerator.div_rem(&gcd)?;
+        let (mut denominator, _) = denominator.div_rem(&gcd)?;
+        if denominator.is_negative() {
+            numerator = -&numerator;
+            denominator = -&denominator;
+        }
+
+        if denominator == BigInt::from(1) {
+            Some(numerator.into())
+        } else {
+            Some(Number::Ratio(Ratio {
+                numerator,
+                denominator,
+            }))
+        }
+    }
+}
+
+impl Ratio {
+    pub fn numerator(&self) -> &BigInt {
+        &self.numerator
+    }
+
+    pub fn denominator(&self) -> &BigInt {
+        &self.denominator
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numerator.to_f64() / self.denominator.to_f64()
+    }
+}
+
+impl Display for Ratio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+impl From<BigInt> for Number {
+    fn from(value: BigInt) -> Self {
+        match value.to_i64() {
+            Some(value) => Number::Int(value),
+            None => Number::BigInt(value),
+        }
+    }
+}
+
+impl Number {
+    /// Returns the numerator and the denominator of an exact number.
+    fn exact(&self) -> Option<Fraction> {
+        match self {
+            Number::Int(value) => Some((BigInt::from(*value), BigInt::from(1))),
+            Number::BigInt(value) => Some((value.clone(), BigInt::from(1))),
+            Number::Ratio(ratio) => Some((ratio.numerator.clone(), ratio.denominator.clone())),
+            Number::Float(_) => None,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Int(value) => *value as f64,
+            Number::BigInt(value) => value.to_f64(),
+            Number::Ratio(ratio) => ratio.to_f64(),
+            Number::Float(value) => *value,
+        }
+    }
+
+    /// Returns if the number is an exact zero.
+    fn is_exact_zero(&self) -> bool {
+        match self {
+            Number::Int(value) => *value == 0,
+            _ => false,
+        }
+    }
+
+    /// Applies an operation to the numbers. Integers that fit in an `i64` take the fast
+    /// path, and are promoted into exact arithmetic when it overflows. If any of the
+    /// numbers is a float, the result is a float.
+    fn apply(
+        self,
+        other: Number,
+        int: fn(i64, i64) -> Option<i64>,
+        exact: fn(Fraction, Fraction) -> Option<Number>,
+        float: fn(f64, f64) -> f64,
+    ) -> Number {
+        if let (Number::Int(a), Number::Int(b)) = (&self, &other) {
+            if let Some(result) = int(*a, *b) {
+                return Number::Int(result);
+            }
+        }
+
+        match (self.exact(), other.exact()) {
+            (Some(a), Some(b)) => exact(a, b).expect("the divisor shouldn't be zero"),
+            _ => Number::Float(float(self.to_f64(), other.to_f64())),
+        }
+    }
+
+    /// Divides the numbers, exact numbers are divided into a [Ratio] if the result isn't
+    /// an integer.
+    pub fn divide(self, other: Number) -> Result<Number, EvalError> {
+        if other.is_exact_zero() {
+            return Err(EvalError::DivisionByZero(SrcPos::default()));
+        }
+
+        Ok(self.apply(
+            other,
+            |a, b| (a.checked_rem(b)? == 0).then_some(a.checked_div(b)?),
+            |(a, b), (c, d)| Number::ratio(&a * &d, &b * &c),
+            |a, b| a / b,
+        ))
+    }
+
+    /// Returns the remainder of the division rounding towards zero, that has the sign of
+    /// the dividend.
+    pub fn remainder(self, other: Number) -> Result<Number, EvalError> {
+        self.remainder_with(other, false)
+    }
+
+    /// Returns the remainder of the division rounding towards negative infinity, that has
+    /// the sign of the divisor.
+    pub fn modulo(self, other: Number) -> Result<Number, EvalError> {
+        self.remainder_with(other, true)
+    }
+
+    /// Computes `a - b * q`, where `q` is the quotient of the division rounded towards
+    /// zero, or towards negative infinity if `floor` is set.
+    fn remainder_with(self, other: Number, floor: bool) -> Result<Number, EvalError> {
+        if other.is_exact_zero() {
+            return Err(EvalError::DivisionByZero(SrcPos::default()));
+        }
+
+        let quotient = match self.clone().divide(other.clone())? {
+            Number::Float(quotient) if floor => Number::Float(quotient.floor()),
+            Number::Float(quotient) => Number::Float(quotient.trunc()),
+            Number::Ratio(ratio) => {
+                let (quotient, _) = ratio.numerator.div_rem(&ratio.denominator).unwrap();
+                // The denominator is positive, so the quotient is rounded up if the ratio
+                // is negative.
+                if floor && ratio.numerator.is_negative() {
+                    (&quotient - &BigInt::from(1)).into()
+                } else {
+                    quotient.into()
+                }
+            }
+            integer => integer,
+        };
+
+        Ok(self - other * quotient)
+    }
+}
+
+impl Neg for Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        Number::Int(0) - self
+    }
+}
+
+impl Add for Number {
+    type Output = Number;
+
+    fn add(self, other: Number) -> Number {
+        self.apply(
+            other,
+            i64::checked_add,
+            |(a, b), (c, d)| Number::ratio(&(&a * &d) + &(&c * &b), &b * &d),
+            |a, b| a + b,
+        )
+    }
+}
+
+impl Sub for Number {
+    type Output = Number;
+
+    fn sub(self, other: Number) -> Number {
+        self.apply(
+            other,
+            i64::checked_sub,
+            |(a, b), (c, d)| Number::ratio(&(&a * &d) - &(&c * &b), &b * &d),
+            |a, b| a - b,
+        )
+    }
+}
+
+impl Mul for Number {
+    type Output = Number;
+
+    fn mul(self, other: Number) -> Number {
+        self.apply(
+            other,
+            i64::checked_mul,
+            |(a, b), (c, d)| Number::ratio(&a * &c, &b * &d),
+            |a, b| a * b,
+        )
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Number {
+    /// Compares the numbers by their values, across all the representations. Exact
+    /// numbers are compared exactly, and floats are compared as floats.
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.exact(), other.exact()) {
+            // The denominators are positive, so the cross products keep the order.
+            (Some((a, b)), Some((c, d))) => Some((&a * &d).cmp(&(&c * &b))),
+            _ => self.to_f64().partial_cmp(&other.to_f64()),
+        }
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Int(value) => write!(f, "{value}"),
+            Number::BigInt(value) => write!(f, "{value}"),
+            Number::Ratio(ratio) => write!(f, "{ratio}"),
+            Number::Float(value) => write!(f, "{value:?}"),
+        }
+    }
+}
+
+impl TryFrom<Value> for Number {
+    type Error = EvalError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Int(value) => Ok(Number::Int(value)),
+            Value::BigInt(value) => Ok(Number::BigInt(value)),
+            Value::Ratio(ratio) => Ok(Number::Ratio(ratio)),
+            Value::Float(value) => Ok(Number::Float(value)),
+            _ => Err(EvalError::ExpectedNumber(SrcPos::default())),
+        }
+    }
+}
+
+impl From<Number> for Value {
+    fn from(number: Number) -> Self {
+        match number {
+            Number::Int(value) => Value::Int(value),
+            Number::BigInt(value) => Value::BigInt(value),
+            Number::Ratio(ratio) => Value::Ratio(ratio),
+            Number::Float(value) => Value::Float(value),
+        }
+    }
+}
+
+/// Tests for the arithmetic of the numeric tower.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(value: i64) -> Number {
+        Number::Int(value)
+    }
+
+    #[test]
+    fn promotes_to_big_integers() {
+        let max = int(i64::MAX);
+        let big = max.clone() * max.clone();
+
+        assert_eq!(big.to_string(), "85070591730234615847396907784232501249");
+        assert_eq!((max.clone() + int(1)).to_string(), "9223372036854775808");
+        assert_eq!((-int(i64::MIN)).to_string(), "9223372036854775808");
+        assert_eq!(
+            (int(0) - big.clone()).to_string(),
+            "-85070591730234615847396907784232501249"
+        );
+        assert!(matches!(
+            big.clone().divide(max.clone()),
+            Ok(Number::Int(i64::MAX))
+        ));
+        assert!(matches!(
+            (max.clone() + int(1)) - int(1),
+            Number::Int(i64::MAX)
+        ));
+    }
+
+    #[test]
+    fn parses_big_integers() {
+        let parse = |string: &str, radix: u32| BigInt::from_str_radix(string, radix);
+
+        assert_eq!(
+            parse("-85070591730234615847396907784232501249", 10).map(|n| n.to_string()),
+            Some("-85070591730234615847396907784232501249".to_string())
+        );
+        assert_eq!(
+            parse("FFFFFFFFFFFFFFFF", 16).map(|n| n.to_string()),
+            Some("18446744073709551615".to_string())
+        );
+        assert_eq!(parse("-0", 10), Some(BigInt::from(0)));
+        assert_eq!(parse("12a", 10), None);
+        assert_eq!(parse("-", 10), None);
+    }
+
+    #[test]
+    fn divides_into_ratios() {
+        let ratio = int(6).divide(int(-4)).unwrap();
+
+        assert_eq!(ratio.to_string(), "-3/2");
+        assert_eq!((ratio.clone() * int(2)).to_string(), "-3");
+        assert_eq!(
+            (ratio.clone() + int(1).divide(int(2)).unwrap()).to_string(),
+            "-1"
+        );
+        assert_eq!(ratio.clone().modulo(int(1)).unwrap().to_string(), "1/2");
+        assert_eq!(ratio.remainder(int(1)).unwrap().to_string(), "-1/2");
+        assert!(matches!(
+            int(1).divide(int(0)),
+            Err(EvalError::DivisionByZero(_))
+        ));
+    }
+
+    #[test]
+    fn compares_across_representations() {
+        let big = int(i64::MAX) + int(1);
+        let half = int(1).divide(int(2)).unwrap();
+
+        assert!(big > int(i64::MAX));
+        assert!(matches!(int(0) - big.clone(), Number::Int(i64::MIN)));
+        assert!(int(-1) - big.clone() < int(i64::MIN));
+        assert!(half < int(1));
+        assert!(half > Number::Float(0.25));
+        assert_eq!(half, Number::Float(0.5));
+        assert_eq!(int(2), Number::Float(2.0));
+    }
+
+    #[test]
+    fn divides_big_magnitudes() {
+        let ten = BigInt::from(10);
+        let mut big = BigInt::from(1);
+        for _ in 0..40 {
+            big = &big * &ten;
+        }
+        let divisor = &BigInt::from(i64::MAX) * &BigInt::from(3);
+        let (quotient, remainder) = big.div_rem(&divisor).unwrap();
+
+        assert_eq!(&(&quotient * &divisor) + &remainder, big);
+        assert!(remainder < divisor);
+        assert_eq!(big.gcd(&BigInt::from(-250)), BigInt::from(250));
+    }
+}
@@ -1,131 +1,973 @@
-use std::{iter::Peekable, str::Chars};
+use std::iter::Peekable;
 
-use crate::{SrcPos, Term, Expr, keyword};
+use miette::Diagnostic;
+use thiserror::Error;
 
-pub fn is_identifier_char(c: char) -> bool {
-    c != ' ' && c != '\n' && c != '\t' && c != '(' && c != ')' && c != '"' && c != ';'
+use crate::{
+    keyword,
+    lexer::{Lexer, Token, TokenKind},
+    source::FileId,
+    symbol::Symbol,
+    Expr, SrcPos, Term, Trivia,
+};
+
+/// Errors that can occur while parsing, all of them carry the position of the offending
+/// token.
+#[derive(Error, Diagnostic, Debug, Clone, PartialEq)]
+pub enum ParseError {
+    #[error("unexpected end of file")]
+    #[diagnostic(
+        code(soft::parser::unexpected_end_of_file),
+        help("there is probably a missing `)` or `]`")
+    )]
+    UnexpectedEndOfFile(#[label("the file ends here")] SrcPos),
+
+    #[error("unterminated string literal")]
+    #[diagnostic(
+        code(soft::parser::unterminated_string),
+        help("close the string with a `\"`")
+    )]
+    UnterminatedString(#[label("this string is never closed")] SrcPos),
+
+    #[error("unterminated block comment")]
+    #[diagnostic(
+        code(soft::parser::unterminated_comment),
+        help("close the comment with a `|#`, block comments can be nested")
+    )]
+    UnterminatedComment(#[label("this comment is never closed")] SrcPos),
+
+    #[error("invalid escape sequence")]
+    #[diagnostic(
+        code(soft::parser::invalid_escape),
+        help("valid escapes are `\\\"`, `\\\\`, `\\n`, `\\t`, `\\r` and `\\u{{...}}`")
+    )]
+    InvalidEscape(#[label("invalid escape")] SrcPos),
+
+    #[error("invalid character literal")]
+    #[diagnostic(
+        code(soft::parser::invalid_character),
+        help("characters are written like `\\a`, `\\newline` or `\\u{{1F600}}`")
+    )]
+    InvalidCharacter(#[label("invalid character")] SrcPos),
+
+    #[error("unbalanced brackets, expected `{0}`")]
+    #[diagnostic(code(soft::parser::unbalanced_brackets))]
+    UnbalancedBrackets(char, #[label("expected `{0}` instead")] SrcPos),
+
+    #[error("unexpected `{0}`")]
+    #[diagnostic(
+        code(soft::parser::unexpected_closing_bracket),
+        help("remove the extra closing bracket")
+    )]
+    UnexpectedClosingBracket(char, #[label("nothing to close here")] SrcPos),
+
+    #[error("invalid digit in base {0} literal")]
+    #[diagnostic(
+        code(soft::parser::invalid_digit),
+        help("integers are written like `255`, `0xFF`, `0o377` or `0b1111_1111`")
+    )]
+    InvalidDigit(u32, #[label("this literal has invalid digits")] SrcPos),
+
+    #[error("invalid ratio literal")]
+    #[diagnostic(
+        code(soft::parser::invalid_ratio),
+        help("ratios are written like `1/2`, and their denominator can't be zero")
+    )]
+    InvalidRatio(#[label("this ratio divides by zero")] SrcPos),
+
+    #[error("invalid float literal")]
+    #[diagnostic(
+        code(soft::parser::invalid_float),
+        help("floats are written like `1.5`, `1e10` or `1.5e-3`")
+    )]
+    InvalidFloat(#[label("invalid float")] SrcPos),
+
+    #[error("map literal has an odd number of forms")]
+    #[diagnostic(
+        code(soft::parser::odd_map_entries),
+        help("maps are written with a value for every key, like `{{:a 1 :b 2}}`")
+    )]
+    OddMapEntries(#[label("this key has no value")] SrcPos),
+
+    #[error("expected a term after reader macro")]
+    #[diagnostic(code(soft::parser::expected_term))]
+    ExpectedTerm(#[label("this reader macro needs a term after it")] SrcPos),
 }
 
+impl ParseError {
+    /// Returns the position of the offending token.
+    pub fn src_pos(&self) -> &SrcPos {
+        match self {
+            ParseError::UnexpectedEndOfFile(src_pos)
+            | ParseError::UnterminatedString(src_pos)
+            | ParseError::UnterminatedComment(src_pos)
+            | ParseError::InvalidEscape(src_pos)
+            | ParseError::InvalidCharacter(src_pos)
+            | ParseError::UnbalancedBrackets(_, src_pos)
+            | ParseError::UnexpectedClosingBracket(_, src_pos)
+            | ParseError::InvalidDigit(_, src_pos)
+            | ParseError::InvalidRatio(src_pos)
+            | ParseError::InvalidFloat(src_pos)
+            | ParseError::OddMapEntries(src_pos)
+            | ParseError::ExpectedTerm(src_pos) => src_pos,
+        }
+    }
+
+    /// Returns the position of the offending token, to move it.
+    pub fn src_pos_mut(&mut self) -> &mut SrcPos {
+        match self {
+            ParseError::UnexpectedEndOfFile(src_pos)
+            | ParseError::UnterminatedString(src_pos)
+            | ParseError::UnterminatedComment(src_pos)
+            | ParseError::InvalidEscape(src_pos)
+            | ParseError::InvalidCharacter(src_pos)
+            | ParseError::UnbalancedBrackets(_, src_pos)
+            | ParseError::UnexpectedClosingBracket(_, src_pos)
+            | ParseError::InvalidDigit(_, src_pos)
+            | ParseError::InvalidRatio(src_pos)
+            | ParseError::InvalidFloat(src_pos)
+            | ParseError::OddMapEntries(src_pos)
+            | ParseError::ExpectedTerm(src_pos) => src_pos,
+        }
+    }
+}
+
+impl From<ParseError> for Expr {
+    fn from(error: ParseError) -> Self {
+        match error {
+            ParseError::UnexpectedEndOfFile(_) => keyword!("parser.error/unexpected-end-of-file"),
+            ParseError::UnterminatedString(_) => keyword!("parser.error/unterminated-string"),
+            ParseError::UnterminatedComment(_) => keyword!("parser.error/unterminated-comment"),
+            ParseError::InvalidEscape(_) => keyword!("parser.error/invalid-escape"),
+            ParseError::InvalidCharacter(_) => keyword!("parser.error/invalid-character"),
+            ParseError::UnbalancedBrackets(..) => keyword!("parser.error/unbalanced-brackets"),
+            ParseError::UnexpectedClosingBracket(..) => {
+                keyword!("parser.error/unexpected-closing-bracket")
+            }
+            ParseError::InvalidDigit(..) => keyword!("parser.error/invalid-digit"),
+            ParseError::InvalidRatio(_) => keyword!("parser.error/invalid-ratio"),
+            ParseError::InvalidFloat(_) => keyword!("parser.error/invalid-float"),
+            ParseError::OddMapEntries(_) => keyword!("parser.error/odd-map-entries"),
+            ParseError::ExpectedTerm(_) => keyword!("parser.error/expected-term"),
+        }
+    }
+}
+
+pub use crate::lexer::is_identifier_char;
+
 pub struct Parser<'a> {
-    pub peekable: Peekable<Chars<'a>>,
+    pub lexer: Peekable<Lexer<'a>>,
     pub string: &'a str,
+
+    /// The end of the last consumed token.
     pub index: usize,
+    pub file: FileId,
+
+    /// If the parser is in recovery mode, errors are collected into [Parser::errors]
+    /// and replaced by [Term::Error] nodes instead of aborting the parsing.
+    pub recovering: bool,
+    pub errors: Vec<ParseError>,
+
+    /// The closing delimiters of the lists and vectors being parsed, used to resynchronise
+    /// in recovery mode.
+    pub delimiters: Vec<char>,
+
+    /// If the parser is in lossless mode, comments and blank lines are kept in the tree
+    /// as [Term::Trivia] nodes, and literals as [Term::Verbatim] nodes with their text, so
+    /// the source can be formatted without losing them.
+    pub lossless: bool,
+}
+
+/// Returns the character of a closing delimiter token.
+fn closing_delimiter(kind: &TokenKind) -> Option<char> {
+    match kind {
+        TokenKind::RParen => Some(')'),
+        TokenKind::RBracket => Some(']'),
+        TokenKind::RBrace => Some('}'),
+        _ => None,
+    }
 }
 
 impl<'a> Parser<'a> {
-    pub fn bump(&mut self) -> Option<char> {
-        let c = self.peekable.next()?;
-        self.index += c.len_utf8();
-        Some(c)
+    pub fn new(string: &'a str) -> Self {
+        Self::with_file(FileId::default(), string)
+    }
+
+    /// Creates a parser for the text of a file registered in the [crate::source::SourceMap],
+    /// so the positions of the terms can be resolved into lines and columns.
+    pub fn with_file(file: FileId, string: &'a str) -> Self {
+        Self {
+            lexer: Lexer::with_file(file, string).peekable(),
+            string,
+            index: 0,
+            file,
+            recovering: false,
+            errors: Vec::new(),
+            delimiters: Vec::new(),
+            lossless: false,
+        }
     }
 
-    pub fn peek(&mut self) -> Option<char> {
-        self.peekable.peek().copied()
+    pub fn bump(&mut self) -> Option<Token> {
+        let token = self.lexer.next()?;
+        self.index = token.src_pos.byte.end;
+        Some(token)
     }
 
-    pub fn accumulate(&mut self, mut f: impl FnMut(char) -> bool) -> String {
-        let mut string = String::new();
+    pub fn peek(&mut self) -> Option<&TokenKind> {
+        self.lexer.peek().map(|token| &token.kind)
+    }
 
+    /// Creates a position from `start` until the current index.
+    fn src_pos(&self, start: usize) -> SrcPos {
+        SrcPos {
+            byte: start..self.index,
+            file: self.file,
+        }
+    }
+
+    /// Skips whitespace, comments and datum comments until the next meaningful token.
+    pub fn skip_trivia(&mut self) -> Result<(), ParseError> {
         loop {
             match self.peek() {
-                Some(c) if f(c) => string.push(self.bump().unwrap()),
-                _ => break,
+                Some(TokenKind::Whitespace | TokenKind::Comment(_)) => {
+                    self.bump();
+                }
+                Some(TokenKind::DatumComment) => self.skip_datum()?,
+                _ => return Ok(()),
             }
         }
-
-        string
     }
 
-    pub fn parse(&mut self) -> Result<Term, Expr> {
+    /// Skips a datum comment like `#_(a b)`, with the whole form after the `#_`.
+    fn skip_datum(&mut self) -> Result<(), ParseError> {
         let start = self.index;
+        self.bump();
+        self.skip_trivia()?;
 
-        let result = match self.peek() {
-            Some(c) if c.is_whitespace() => {
-                self.accumulate(|c| c.is_whitespace());
-                self.parse()
+        match self.peek() {
+            None => Err(ParseError::UnexpectedEndOfFile(self.src_pos(self.index))),
+            Some(TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace) => {
+                Err(ParseError::ExpectedTerm(self.src_pos(start)))
             }
-            Some(';') => {
-                self.accumulate(|c| c != '\n');
-                self.parse()
-            }
-            Some('"') => {
-                self.bump();
-                let string = self.accumulate(|c| c != '"');
+            Some(_) => self.parse().map(drop),
+        }
+    }
 
-                if self.bump() != Some('"') {
-                    return Err(keyword!("parser.error/unexpected-quote"));
+    /// Skips the trivia like [Parser::skip_trivia], but in lossless mode the comments and
+    /// blank lines are pushed into the terms of the enclosing sequence. In recovery mode,
+    /// an invalid datum comment is pushed as a [Term::Error] node.
+    fn collect_trivia(&mut self, terms: &mut Vec<Term>) -> Result<(), ParseError> {
+        let mut newlines = 0;
+        loop {
+            let start = self.index;
+            match self.peek() {
+                Some(TokenKind::Whitespace) => {
+                    self.bump();
+                    newlines += self.string[start..self.index].matches('\n').count();
+                    let is_after_term = matches!(terms.last(), Some(term) if !term.is_blank_line());
+                    if newlines > 1 && is_after_term && self.lossless {
+                        let blank_line = Term::Trivia(Trivia::BlankLine);
+                        terms.push(Term::SrcPos(self.src_pos(start), Box::new(blank_line)));
+                    }
                 }
-
-                Ok(Term::String(string))
-            }
-            Some(':') => {
-                self.bump();
-                let string = self.accumulate(is_identifier_char);
-                Ok(Term::Atom(string))
+                Some(TokenKind::Comment(_) | TokenKind::DatumComment) => {
+                    // Datum comments are kept as comments with the text of the form.
+                    if let Some(TokenKind::DatumComment) = self.peek() {
+                        match self.skip_datum() {
+                            Ok(()) => {}
+                            Err(error) if self.recovering => {
+                                self.errors.push(error);
+                                terms
+                                    .push(Term::SrcPos(self.src_pos(start), Box::new(Term::Error)));
+                                newlines = 0;
+                                continue;
+                            }
+                            Err(error) => return Err(error),
+                        }
+                    } else {
+                        self.bump();
+                    }
+                    if !self.lossless {
+                        continue;
+                    }
+                    let text = self.string[start..self.index].trim_end().to_string();
+                    let comment = if newlines == 0 && !terms.is_empty() {
+                        Trivia::TrailingComment(text)
+                    } else {
+                        Trivia::Comment(text)
+                    };
+                    terms.push(Term::SrcPos(
+                        self.src_pos(start),
+                        Box::new(Term::Trivia(comment)),
+                    ));
+                    newlines = 0;
+                }
+                _ => return Ok(()),
             }
-            Some(c) if c.is_ascii_digit() => {
-                let string = self.accumulate(|c| c.is_ascii_digit());
-                Ok(Term::Int(string.parse().unwrap()))
+        }
+    }
+
+    /// Removes the blank line at the end of a sequence, they're only kept between terms.
+    fn trim_trivia(&self, terms: &mut Vec<Term>) {
+        if matches!(terms.last(), Some(term) if term.is_blank_line()) {
+            terms.pop();
+        }
+    }
+
+    /// Parses every top-level term until the end of the input, each one of them keeps
+    /// its own [SrcPos], and trailing whitespace and comments are ignored.
+    pub fn parse_program(&mut self) -> Result<Vec<Term>, ParseError> {
+        let mut terms = Vec::new();
+
+        loop {
+            self.collect_trivia(&mut terms)?;
+            if self.peek().is_none() {
+                break;
             }
-            Some('(') => {
-                self.bump();
-                let mut terms = Vec::new();
-
-                loop {
-                    match self.peek() {
-                        Some(c) if c.is_whitespace() => {
-                            self.accumulate(|c| c.is_whitespace());
-                        }
-                        Some(')') => {
-                            self.bump();
-                            break;
-                        }
-                        Some(_) => {
-                            terms.push(self.parse()?);
-                        }
-                        None => return Err(keyword!("parser.error/unexpected-end-of-file")),
+            terms.push(self.parse()?);
+        }
+
+        self.trim_trivia(&mut terms);
+        Ok(terms)
+    }
+
+    /// Parses every top-level term like [Parser::parse_program], but keeping the comments
+    /// and blank lines as [Term::Trivia] nodes.
+    pub fn parse_program_lossless(&mut self) -> Result<Vec<Term>, ParseError> {
+        self.lossless = true;
+        self.parse_program()
+    }
+
+    /// Parses every top-level term like [Parser::parse_program], but doesn't stop at the
+    /// first error: the invalid parts of the tree are replaced by [Term::Error] nodes, and
+    /// all the errors are returned together with the partial terms.
+    pub fn parse_program_with_recovery(&mut self) -> (Vec<Term>, Vec<ParseError>) {
+        self.recovering = true;
+        let terms = self
+            .parse_program()
+            .expect("errors are collected in recovery mode");
+        (terms, std::mem::take(&mut self.errors))
+    }
+
+    /// Parses the elements of a list or a vector until the `close` delimiter, the opening
+    /// delimiter should be already consumed.
+    fn parse_sequence(&mut self, close: char) -> Result<Vec<Term>, ParseError> {
+        self.delimiters.push(close);
+        let terms = self.parse_elements(close);
+        self.delimiters.pop();
+        terms
+    }
+
+    fn parse_elements(&mut self, close: char) -> Result<Vec<Term>, ParseError> {
+        let mut terms = Vec::new();
+
+        loop {
+            self.collect_trivia(&mut terms)?;
+            let Some(kind) = self.peek() else {
+                let error = ParseError::UnexpectedEndOfFile(self.src_pos(self.index));
+                if !self.recovering {
+                    return Err(error);
+                }
+                self.errors.push(error);
+                break;
+            };
+
+            match closing_delimiter(kind) {
+                Some(c) if c == close => {
+                    self.bump();
+                    break;
+                }
+                // In recovery mode, a closing delimiter of an enclosing list closes the
+                // current one too, otherwise it's just skipped.
+                Some(c) if self.recovering => {
+                    let start = self.index;
+                    let is_enclosing = self.delimiters.contains(&c);
+                    if !is_enclosing {
+                        self.bump();
+                    }
+                    let error = ParseError::UnbalancedBrackets(close, self.src_pos(start));
+                    self.errors.push(error);
+                    if is_enclosing {
+                        break;
                     }
                 }
+                Some(_) => {
+                    let start = self.index;
+                    self.bump();
+                    return Err(ParseError::UnbalancedBrackets(close, self.src_pos(start)));
+                }
+                None => {
+                    terms.push(self.parse()?);
+                }
+            }
+        }
 
-                Ok(Term::List(terms))
+        self.trim_trivia(&mut terms);
+        Ok(terms)
+    }
+
+    /// Parses the term after an already consumed reader macro prefix, like `'x`, that
+    /// starts at `start`, and wraps it into a `(name term)` form. The trivia between the
+    /// prefix and the term, like in `'#_a b`, is kept before the term in lossless mode.
+    fn parse_prefixed(&mut self, name: &str, start: usize) -> Result<Term, ParseError> {
+        let head = Term::SrcPos(self.src_pos(start), Box::new(Term::Identifier(name.into())));
+        let mut terms = vec![head];
+        self.collect_trivia(&mut terms)?;
+
+        match self.peek() {
+            Some(TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace) => {
+                Err(ParseError::ExpectedTerm(self.src_pos(start)))
             }
+            None => Err(ParseError::UnexpectedEndOfFile(self.src_pos(self.index))),
             Some(_) => {
-                let string = self.accumulate(is_identifier_char);
-                Ok(Term::Identifier(string))
+                terms.push(self.parse()?);
+                Ok(Term::List(terms.into()))
+            }
+        }
+    }
+
+    /// Parses the entries of a map literal like `{:a 1 :b 2}`, that are kept flat as keys
+    /// followed by their values.
+    fn parse_map(&mut self) -> Result<Term, ParseError> {
+        let entries = self.parse_sequence('}')?;
+        let forms = entries
+            .iter()
+            .filter(|term| !matches!(term, Term::SrcPos(_, box Term::Trivia(_))))
+            .collect::<Vec<_>>();
+
+        match forms.last() {
+            Some(Term::SrcPos(src_pos, _)) if !forms.len().is_multiple_of(2) => {
+                Err(ParseError::OddMapEntries(src_pos.clone()))
+            }
+            _ => Ok(Term::Map(entries.into())),
+        }
+    }
+
+    pub fn parse(&mut self) -> Result<Term, ParseError> {
+        self.skip_trivia()?;
+        let start = self.index;
+
+        let result = match self.bump().map(|token| token.kind) {
+            Some(TokenKind::String(string)) => Ok(Term::String(string.into())),
+            Some(TokenKind::Char(c)) => Ok(Term::Char(c)),
+            Some(TokenKind::Atom(string)) => Ok(Term::Atom(Symbol::intern(&string))),
+            Some(TokenKind::Identifier(string)) => Ok(Term::Identifier(Symbol::intern(&string))),
+            Some(TokenKind::Int(n)) => Ok(Term::Int(n)),
+            Some(TokenKind::BigInt(n)) => Ok(Term::BigInt(n)),
+            Some(TokenKind::Ratio(n)) => Ok(Term::Ratio(n)),
+            Some(TokenKind::Float(n)) => Ok(Term::Float(n)),
+            Some(TokenKind::Quote) => self.parse_prefixed("quote", start),
+            Some(TokenKind::Quasiquote) => self.parse_prefixed("quasiquote", start),
+            Some(TokenKind::Unquote) => self.parse_prefixed("unquote", start),
+            Some(TokenKind::UnquoteSplicing) => self.parse_prefixed("unquote-splicing", start),
+            Some(TokenKind::LParen) => self
+                .parse_sequence(')')
+                .map(|terms| Term::List(terms.into())),
+            Some(TokenKind::LBracket) => self
+                .parse_sequence(']')
+                .map(|terms| Term::Vec(terms.into())),
+            Some(TokenKind::LBrace) => self.parse_map(),
+            Some(TokenKind::RParen) => Err(ParseError::UnexpectedClosingBracket(
+                ')',
+                self.src_pos(start),
+            )),
+            Some(TokenKind::RBracket) => Err(ParseError::UnexpectedClosingBracket(
+                ']',
+                self.src_pos(start),
+            )),
+            Some(TokenKind::RBrace) => Err(ParseError::UnexpectedClosingBracket(
+                '}',
+                self.src_pos(start),
+            )),
+            Some(TokenKind::Error(error)) => Err(error),
+            Some(TokenKind::Whitespace | TokenKind::Comment(_) | TokenKind::DatumComment) => {
+                unreachable!("trivia is skipped before parsing a term")
+            }
+            None => Err(ParseError::UnexpectedEndOfFile(self.src_pos(start))),
+        };
+
+        // Literals are printed in a canonical way, so their spelling, like `0xFF`, `\u{41}`
+        // or a string with line breaks, is kept to be formatted as it was written.
+        let result = result.map(|term| match term {
+            Term::Int(_)
+            | Term::BigInt(_)
+            | Term::Ratio(_)
+            | Term::Float(_)
+            | Term::String(_)
+            | Term::Char(_)
+                if self.lossless =>
+            {
+                let text = &self.string[start..self.index];
+                if text == term.to_string() {
+                    term
+                } else {
+                    Term::Verbatim(text.into(), Box::new(term))
+                }
             }
-            None => Err(keyword!("parser.error/unexpected-end-of-file")),
-        }?;
+            term => term,
+        });
 
-        Ok(Term::SrcPos(
-            SrcPos {
-                byte: start..self.index,
-                file: self.string.to_string(),
-            },
-            Box::new(result),
-        ))
+        match result {
+            Ok(term) => Ok(Term::SrcPos(self.src_pos(start), Box::new(term))),
+            Err(error) if self.recovering => {
+                self.errors.push(error);
+                Ok(Term::SrcPos(self.src_pos(start), Box::new(Term::Error)))
+            }
+            Err(error) => Err(error),
+        }
     }
 }
 
-pub fn parse_sexpr(string: &str) -> Result<Term, Expr> {
-    let mut parser = Parser {
-        peekable: string.chars().peekable(),
-        string,
-        index: 0,
-    };
+pub fn parse_sexpr(string: &str) -> Result<Term, ParseError> {
+    Parser::new(string).parse()
+}
+
+/// Parses all the top-level terms of a file.
+pub fn parse_program(string: &str) -> Result<Vec<Term>, ParseError> {
+    Parser::new(string).parse_program()
+}
+
+/// Parses all the top-level terms of a file, keeping the comments and blank lines.
+pub fn parse_program_lossless(string: &str) -> Result<Vec<Term>, ParseError> {
+    Parser::new(string).parse_program_lossless()
+}
 
-    parser.parse()
+/// Parses all the top-level terms of a file, collecting every error instead of stopping
+/// at the first one.
+pub fn parse_program_with_recovery(string: &str) -> (Vec<Term>, Vec<ParseError>) {
+    Parser::new(string).parse_program_with_recovery()
 }
 
 /// Tests for parser of S-expressions.
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
+    use crate::number::{BigInt, Number};
 
     #[test]
     fn parses_string() {
         assert_eq!(
             parse_sexpr(r#""hello world""#).unwrap().unbox(),
-            Term::String("hello world".to_string())
+            Term::String("hello world".into())
+        );
+    }
+
+    #[test]
+    fn parses_vector() {
+        assert_eq!(
+            parse_sexpr("[a [1 2] (b [])]").unwrap().unbox(),
+            Term::Vec(
+                vec![
+                    Term::Identifier("a".into()),
+                    Term::Vec(vec![Term::Int(1), Term::Int(2)].into()),
+                    Term::List(vec![Term::Identifier("b".into()), Term::Vec(vec![].into())].into()),
+                ]
+                .into()
+            )
+        );
+    }
+
+    #[test]
+    fn parses_fun_parameters() {
+        assert_eq!(
+            parse_sexpr("(fun* f [a b] a)").unwrap().unbox(),
+            Term::List(
+                vec![
+                    Term::Identifier("fun*".into()),
+                    Term::Identifier("f".into()),
+                    Term::Vec(
+                        vec![Term::Identifier("a".into()), Term::Identifier("b".into()),].into()
+                    ),
+                    Term::Identifier("a".into()),
+                ]
+                .into()
+            )
+        );
+    }
+
+    #[test]
+    fn parses_map() {
+        assert_eq!(
+            parse_sexpr("{:a 1 \"b\" {}}").unwrap().unbox(),
+            Term::Map(
+                vec![
+                    Term::Atom("a".into()),
+                    Term::Int(1),
+                    Term::String("b".into()),
+                    Term::Map(vec![].into()),
+                ]
+                .into()
+            )
+        );
+        assert!(matches!(
+            parse_sexpr("{:a 1 :b}"),
+            Err(ParseError::OddMapEntries(SrcPos { byte, .. })) if byte == (6..8)
+        ));
+        assert!(matches!(
+            parse_sexpr("{:a 1]"),
+            Err(ParseError::UnbalancedBrackets('}', _))
+        ));
+    }
+
+    #[test]
+    fn fails_on_unbalanced_brackets() {
+        assert!(matches!(
+            parse_sexpr("[a b)"),
+            Err(ParseError::UnbalancedBrackets(']', SrcPos { byte, .. })) if byte == (4..5)
+        ));
+        assert!(matches!(
+            parse_sexpr("(a b]"),
+            Err(ParseError::UnbalancedBrackets(')', _))
+        ));
+        assert!(matches!(
+            parse_sexpr("[a b"),
+            Err(ParseError::UnexpectedEndOfFile(_))
+        ));
+        assert!(matches!(
+            parse_sexpr("  )"),
+            Err(ParseError::UnexpectedClosingBracket(')', SrcPos { byte, .. })) if byte == (2..3)
+        ));
+    }
+
+    #[test]
+    fn parses_big_integers_and_ratios() {
+        let term = parse_sexpr("(a 99999999999999999999 -1/2)")
+            .unwrap()
+            .unbox();
+        let Term::List(elements) = &term else {
+            panic!("expected list");
+        };
+
+        assert!(matches!(elements[1].clone().unbox(), Term::BigInt(_)));
+        assert!(matches!(elements[2].clone().unbox(), Term::Ratio(_)));
+        assert_eq!(term.to_string(), "(a 99999999999999999999 -1/2)");
+        assert!(matches!(
+            parse_sexpr("(a 1/0)"),
+            Err(ParseError::InvalidRatio(SrcPos { byte, .. })) if byte == (3..6)
+        ));
+    }
+
+    #[test]
+    fn converts_errors_into_keywords() {
+        let error = parse_sexpr("\"abc").unwrap_err();
+        assert!(
+            matches!(error, ParseError::UnterminatedString(SrcPos { ref byte, .. }) if *byte == (0..4))
+        );
+        assert_eq!(
+            Term::from(Expr::from(error)).unbox(),
+            Term::Atom("parser.error/unterminated-string".into())
+        );
+    }
+
+    #[test]
+    fn parses_float() {
+        assert_eq!(parse_sexpr("1.5").unwrap().unbox(), Term::Float(1.5));
+        assert_eq!(parse_sexpr("2e3").unwrap().unbox(), Term::Float(2000.0));
+        assert_eq!(parse_sexpr("1.25E-2").unwrap().unbox(), Term::Float(0.0125));
+        assert_eq!(parse_sexpr("42").unwrap().unbox(), Term::Int(42));
+        assert!(parse_sexpr("1.").is_err());
+        assert!(parse_sexpr("1e+").is_err());
+    }
+
+    #[test]
+    fn prints_float() {
+        assert_eq!(Term::Float(1.0).to_string(), "1.0");
+        assert_eq!(Term::Float(0.0125).to_string(), "0.0125");
+        assert_eq!(
+            parse_sexpr(&Term::Float(1e100).to_string())
+                .unwrap()
+                .unbox(),
+            Term::Float(1e100)
         );
     }
+
+    #[test]
+    fn parses_string_escapes() {
+        assert_eq!(
+            parse_sexpr(r#""a \"b\" \\ \n\t\u{1F600}""#)
+                .unwrap()
+                .unbox(),
+            Term::String("a \"b\" \\ \n\t\u{1F600}".into())
+        );
+        assert_eq!(
+            parse_sexpr("\"multi\nline\"").unwrap().unbox(),
+            Term::String("multi\nline".into())
+        );
+    }
+
+    #[test]
+    fn fails_on_invalid_escape() {
+        assert!(parse_sexpr(r#""\q""#).is_err());
+        assert!(parse_sexpr(r#""\u{110000}""#).is_err());
+        assert!(parse_sexpr(r#""\u{41""#).is_err());
+        assert!(parse_sexpr(r#""unterminated"#).is_err());
+    }
+
+    #[test]
+    fn round_trips_string() {
+        let term = Term::String("say \"hi\"\n\t\\ \u{7}".into());
+        assert_eq!(parse_sexpr(&term.to_string()).unwrap().unbox(), term);
+    }
+
+    #[test]
+    fn parses_reader_macros() {
+        let quoted =
+            |name: &str, term: Term| Term::List(vec![Term::Identifier(name.into()), term].into());
+        let a = Term::Identifier("a".into());
+
+        assert_eq!(
+            parse_sexpr("'a").unwrap().unbox(),
+            quoted("quote", a.clone())
+        );
+        assert_eq!(
+            parse_sexpr("`a").unwrap().unbox(),
+            quoted("quasiquote", a.clone())
+        );
+        assert_eq!(
+            parse_sexpr(",a").unwrap().unbox(),
+            quoted("unquote", a.clone())
+        );
+        assert_eq!(
+            parse_sexpr("`(a ,@[a])").unwrap().unbox(),
+            quoted(
+                "quasiquote",
+                Term::List(
+                    vec![
+                        a.clone(),
+                        quoted("unquote-splicing", Term::Vec(vec![a.clone()].into()))
+                    ]
+                    .into()
+                )
+            )
+        );
+        assert!(parse_sexpr("(')").is_err());
+    }
+
+    #[test]
+    fn preserves_reader_macro_positions() {
+        let Term::SrcPos(outer, box Term::List(elements)) = parse_sexpr(",@abc").unwrap() else {
+            panic!("expected list");
+        };
+        let [Term::SrcPos(head, _), Term::SrcPos(term, _)] = &elements[..] else {
+            panic!("expected positions");
+        };
+
+        assert_eq!(outer.byte, 0..5);
+        assert_eq!(head.byte, 0..2);
+        assert_eq!(term.byte, 2..5);
+    }
+
+    #[test]
+    fn parses_program() {
+        let source = "; header\n(a 1)\n\n  [b] ; trailing\n:c\n; end";
+        let terms = parse_program(source).unwrap();
+        let spans = terms
+            .iter()
+            .map(|term| match term {
+                Term::SrcPos(src_pos, _) => &source[src_pos.byte.clone()],
+                _ => panic!("expected position"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(spans, ["(a 1)", "[b]", ":c"]);
+        assert!(parse_program("  ; nothing here\n").unwrap().is_empty());
+        assert!(parse_program("(a) (b").is_err());
+    }
+
+    #[test]
+    fn recovers_from_errors() {
+        let (terms, errors) = parse_program_with_recovery("(a \"\\q\" [b) (c]) ) (d 1.");
+        let terms = terms.into_iter().map(Term::unbox).collect::<Vec<_>>();
+        let identifier = |name: &str| Term::Identifier(name.into());
+
+        assert_eq!(terms, [
+            Term::List(
+                vec![
+                    identifier("a"),
+                    Term::Error,
+                    Term::Vec(vec![identifier("b")].into()),
+                ]
+                .into()
+            ),
+            Term::List(vec![identifier("c")].into()),
+            Term::Error,
+            Term::List(vec![identifier("d"), Term::Error].into()),
+        ]);
+        assert!(matches!(errors.as_slice(), [
+            ParseError::InvalidEscape(_),
+            ParseError::UnbalancedBrackets(']', _),
+            ParseError::UnbalancedBrackets(')', _),
+            ParseError::UnexpectedClosingBracket(')', _),
+            ParseError::InvalidFloat(_),
+            ParseError::UnexpectedEndOfFile(_),
+        ]));
+    }
+
+    #[test]
+    fn recovers_from_unfinished_datum_comments() {
+        let identifier = |name: &str| Term::Identifier(name.into());
+        let list = |terms: Vec<Term>| Term::List(terms.into());
+
+        let (terms, errors) = parse_program_with_recovery("(a) 'x #_");
+        let terms = terms.into_iter().map(Term::unbox).collect::<Vec<_>>();
+        assert_eq!(terms, [
+            list(vec![identifier("a")]),
+            list(vec![identifier("quote"), identifier("x")]),
+            Term::Error,
+        ]);
+        assert!(matches!(errors.as_slice(), [
+            ParseError::UnexpectedEndOfFile(_)
+        ]));
+
+        let (terms, errors) = parse_program_with_recovery("(a) (b #_)");
+        let terms = terms.into_iter().map(Term::unbox).collect::<Vec<_>>();
+        assert_eq!(terms, [
+            list(vec![identifier("a")]),
+            list(vec![identifier("b"), Term::Error]),
+        ]);
+        assert!(matches!(errors.as_slice(), [ParseError::ExpectedTerm(_)]));
+    }
+
+    #[test]
+    fn skips_block_and_datum_comments() {
+        let source = "#| header #| nested |# |#\n(a #_(b [c]) #_ #_ d e f) #_g";
+        let terms = parse_program(source).unwrap();
+        let Term::SrcPos(src_pos, box Term::List(elements)) = &terms[0] else {
+            panic!("expected list");
+        };
+        let Term::SrcPos(f, _) = &elements[1] else {
+            panic!("expected position");
+        };
+
+        assert_eq!(terms.len(), 1);
+        assert_eq!(&source[src_pos.byte.clone()], "(a #_(b [c]) #_ #_ d e f)");
+        assert_eq!(&source[f.byte.clone()], "f");
+        assert_eq!(
+            terms[0].clone().unbox(),
+            Term::List(vec![Term::Identifier("a".into()), Term::Identifier("f".into()),].into())
+        );
+        assert!(matches!(
+            parse_program("(a #_)"),
+            Err(ParseError::ExpectedTerm(SrcPos { byte, .. })) if byte == (3..5)
+        ));
+        assert!(matches!(
+            parse_program("#| a"),
+            Err(ParseError::UnterminatedComment(_))
+        ));
+    }
+
+    #[test]
+    fn keeps_trivia_in_lossless_mode() {
+        let source = ";; header\n\n\n(a ; first\n ; own line\n b)\n";
+        let terms = parse_program_lossless(source).unwrap();
+        let terms = terms.into_iter().map(Term::unbox).collect::<Vec<_>>();
+
+        assert_eq!(terms, [
+            Term::Trivia(Trivia::Comment(";; header".to_string())),
+            Term::Trivia(Trivia::BlankLine),
+            Term::List(
+                vec![
+                    Term::Identifier("a".into()),
+                    Term::Trivia(Trivia::TrailingComment("; first".to_string())),
+                    Term::Trivia(Trivia::Comment("; own line".to_string())),
+                    Term::Identifier("b".into()),
+                ]
+                .into()
+            ),
+        ]);
+
+        let terms = parse_program_lossless("(a #_ (b) c) #| d |#").unwrap();
+        let terms = terms.into_iter().map(Term::unbox).collect::<Vec<_>>();
+
+        assert_eq!(terms, [
+            Term::List(
+                vec![
+                    Term::Identifier("a".into()),
+                    Term::Trivia(Trivia::TrailingComment("#_ (b)".to_string())),
+                    Term::Identifier("c".into()),
+                ]
+                .into()
+            ),
+            Term::Trivia(Trivia::TrailingComment("#| d |#".to_string())),
+        ]);
+
+        let quote = |terms: Vec<Term>| {
+            let head = Term::Identifier("quote".into());
+            Term::List(std::iter::once(head).chain(terms).collect())
+        };
+        let terms = parse_program_lossless("'#_b a").unwrap();
+        assert_eq!(
+            terms[0].clone().unbox(),
+            quote(vec![
+                Term::Trivia(Trivia::TrailingComment("#_b".to_string())),
+                Term::Identifier("a".into()),
+            ])
+        );
+        assert_eq!(
+            parse_sexpr("'#| c |#a").map(Term::unbox),
+            Ok(quote(vec![Term::Identifier("a".into())]))
+        );
+        assert!(matches!(
+            parse_sexpr("'#_b)"),
+            Err(ParseError::ExpectedTerm(SrcPos { byte, .. })) if byte == (0..4)
+        ));
+    }
+
+    /// Generates terms that can be printed and read back, without the errors and trivia,
+    /// that are never read from the source.
+    fn arbitrary_term() -> impl Strategy<Value = Term> {
+        let name = "[a-z*+!?<>=/][a-z0-9*+!?<>=/.-]{0,8}";
+        let leaf = prop_oneof![
+            name.prop_map(|name| Term::Identifier(name.as_str().into())),
+            name.prop_map(|name| Term::Atom(name.as_str().into())),
+            any::<i64>().prop_map(Term::Int),
+            any::<i128>().prop_filter_map("big integers don't fit in an i64", |n| {
+                let n = BigInt::from_str_radix(&n.to_string(), 10)?;
+                n.to_i64().is_none().then_some(Term::BigInt(n))
+            }),
+            (any::<i64>(), 2..i64::MAX).prop_filter_map("ratios aren't integers", |(n, d)| {
+                match Number::ratio(BigInt::from(n), BigInt::from(d)) {
+                    Some(Number::Ratio(ratio)) => Some(Term::Ratio(ratio)),
+                    _ => None,
+                }
+            }),
+            any::<f64>()
+                .prop_filter("floats are finite", |n| n.is_finite())
+                .prop_map(Term::Float),
+            any::<String>().prop_map(|string| Term::String(string.into())),
+            any::<char>().prop_map(Term::Char),
+        ];
+
+        leaf.prop_recursive(4, 64, 8, |term| {
+            let terms = prop::collection::vec(term, 0..8);
+            prop_oneof![
+                terms.clone().prop_map(|terms| Term::List(terms.into())),
+                terms.clone().prop_map(|terms| Term::Vec(terms.into())),
+                terms.prop_map(|mut terms| {
+                    terms.truncate(terms.len() / 2 * 2);
+                    Term::Map(terms.into())
+                }),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn reads_back_printed_terms(term in arbitrary_term(), width in 0..100usize) {
+            let style = crate::pretty::Style { width, ..Default::default() };
+            let printed = term.pretty(&style);
+
+            prop_assert_eq!(parse_sexpr(&printed).map(Term::unbox), Ok(term), "{}", printed);
+        }
+
+        #[test]
+        fn never_panics(source in r#"[()\[\]{}"'`,@#_|;\\ \n:a-z0-9.xeu+-]{0,64}"#) {
+            let _ = parse_sexpr(&source).map(|term| term.to_string());
+            let _ = parse_program_lossless(&source);
+            let _ = parse_program_with_recovery(&source);
+        }
+    }
 }
@@ -0,0 +1,517 @@
+use std::collections::HashMap;
+
+use crate::{escape_char, escape_string, Term, Trivia};
+
+/// A document to be laid out, it's the intermediate representation between a [Term] and
+/// its text, following Wadler's "A prettier printer".
+#[derive(Debug, Clone, PartialEq)]
+pub enum Doc {
+    Text(String),
+
+    /// A space if the enclosing group fits in the line, or a line break otherwise.
+    Line,
+
+    /// A line break that is never laid out as a space, the enclosing groups are broken.
+    HardLine,
+    Concat(Vec<Doc>),
+
+    /// Increases the indentation of the line breaks inside of the document.
+    Nest(usize, Box<Doc>),
+
+    /// Sets the indentation of the line breaks inside of the document to the current
+    /// column.
+    Align(Box<Doc>),
+
+    /// Lays out the document in a single line if it fits, or breaks all of its lines.
+    Group(Box<Doc>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Layout options for printing terms.
+#[derive(Debug, Clone)]
+pub struct Style {
+    /// The maximum width of the lines, it can be exceeded by atoms that don't fit.
+    pub width: usize,
+
+    /// The indentation of the bodies of special forms.
+    pub indent: usize,
+
+    /// Special forms, with the number of arguments that are kept in the same line of
+    /// the head, the remaining ones are the body and are indented by [Style::indent].
+    pub special_forms: HashMap<String, usize>,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            indent: 2,
+            special_forms: HashMap::from([
+                ("fun*".to_string(), 2),
+                ("def*".to_string(), 1),
+                ("defmacro*".to_string(), 1),
+            ]),
+        }
+    }
+}
+
+impl Doc {
+    pub fn text(text: impl Into<String>) -> Self {
+        Doc::Text(text.into())
+    }
+
+    pub fn group(self) -> Self {
+        Doc::Group(Box::new(self))
+    }
+
+    pub fn align(self) -> Self {
+        Doc::Align(Box::new(self))
+    }
+
+    pub fn nest(self, indent: usize) -> Self {
+        Doc::Nest(indent, Box::new(self))
+    }
+
+    /// Lays out the document in lines of at most `width` columns, when possible.
+    pub fn render(&self, width: usize) -> String {
+        let mut output = String::new();
+        let mut column = 0;
+        let mut stack = vec![(0, Mode::Break, self)];
+
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Doc::Text(text) => {
+                    output.push_str(text);
+                    // Texts can have line breaks, like the strings that span many lines.
+                    match text.rsplit_once('\n') {
+                        Some((_, last_line)) => column = last_line.chars().count(),
+                        None => column += text.chars().count(),
+                    }
+                }
+                Doc::Line if mode == Mode::Flat => {
+                    output.push(' ');
+                    column += 1;
+                }
+                Doc::Line | Doc::HardLine => {
+                    output.truncate(output.trim_end_matches(' ').len());
+                    output.push('\n');
+                    output.push_str(&" ".repeat(indent));
+                    column = indent;
+                }
+                Doc::Concat(docs) => {
+                    stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+                }
+                Doc::Nest(nest, doc) => stack.push((indent + nest, mode, doc)),
+                Doc::Align(doc) => stack.push((column, mode, doc)),
+                Doc::Group(doc) if mode == Mode::Flat => stack.push((indent, mode, doc)),
+                Doc::Group(doc) => {
+                    let remaining = width.saturating_sub(column);
+                    let mode = if fits(width, remaining, doc, &stack) {
+                        Mode::Flat
+                    } else {
+                        Mode::Break
+                    };
+                    stack.push((indent, mode, doc));
+                }
+            }
+        }
+
+        output
+    }
+}
+
+/// Checks if the document fits in the remaining width when laid out in a single line,
+/// together with what follows it until the next line break.
+fn fits(width: usize, mut remaining: usize, doc: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut stack = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+
+    loop {
+        let (mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some((_, mode, doc)) => (*mode, *doc),
+                None => return true,
+            },
+        };
+
+        match doc {
+            // The last line of a text with line breaks starts at the first column.
+            Doc::Text(text) => match text.split_once('\n') {
+                Some((first_line, _)) if first_line.chars().count() > remaining => return false,
+                Some(_) => {
+                    let (_, last_line) = text.rsplit_once('\n').unwrap();
+                    match width.checked_sub(last_line.chars().count()) {
+                        Some(width) => remaining = width,
+                        None => return false,
+                    }
+                }
+                None => match remaining.checked_sub(text.chars().count()) {
+                    Some(width) => remaining = width,
+                    None => return false,
+                },
+            },
+            Doc::Line if mode == Mode::Flat => match remaining.checked_sub(1) {
+                Some(width) => remaining = width,
+                None => return false,
+            },
+            Doc::HardLine if mode == Mode::Flat => return false,
+            Doc::Line | Doc::HardLine => return true,
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (mode, doc))),
+            Doc::Nest(_, doc) | Doc::Align(doc) | Doc::Group(doc) => stack.push((mode, doc)),
+        }
+    }
+}
+
+impl Style {
+    /// Converts a term into a document, following the layout rules of the style.
+    pub fn to_doc(&self, term: &Term) -> Doc {
+        match term {
+            Term::SrcPos(_, term) => self.to_doc(term),
+            Term::Vec(elements) => Doc::Concat(vec![
+                Doc::text("["),
+                self.sequence(elements).align(),
+                Doc::text("]"),
+            ])
+            .group(),
+            Term::Map(elements) => Doc::Concat(vec![
+                Doc::text("{"),
+                self.sequence(elements).align(),
+                Doc::text("}"),
+            ])
+            .group(),
+            Term::List(elements) => self.list_to_doc(elements),
+            Term::Trivia(Trivia::Comment(text) | Trivia::TrailingComment(text)) => {
+                Doc::text(text.clone())
+            }
+            Term::Trivia(Trivia::BlankLine) => Doc::text(""),
+            atom => Doc::text(atom_to_string(atom)),
+        }
+    }
+
+    /// Formats the top-level terms of a file, that should be read in lossless mode to
+    /// keep the comments and blank lines.
+    pub fn format_program(&self, terms: &[Term]) -> String {
+        let output = self.sequence(terms).render(self.width);
+        let output = output.trim_end();
+        if output.is_empty() {
+            String::new()
+        } else {
+            format!("{output}\n")
+        }
+    }
+
+    /// Lays out the terms separated by [Doc::Line]. Comments are kept in their own lines,
+    /// or after the previous term if they're trailing comments, and blank lines are kept.
+    /// Datum and block comments in a single line, like `#_a`, are laid out like terms.
+    fn sequence(&self, terms: &[Term]) -> Doc {
+        let mut docs = Vec::new();
+        let mut is_after_comment = false;
+        let mut is_after_blank_line = false;
+
+        for term in terms {
+            match trivia(term) {
+                Some(Trivia::BlankLine) => {
+                    is_after_blank_line = true;
+                    continue;
+                }
+                Some(Trivia::TrailingComment(text)) if !docs.is_empty() && !is_after_comment => {
+                    docs.push(Doc::text(format!(" {text}")));
+                    is_after_comment = !is_inline_comment(text);
+                    continue;
+                }
+                _ => {}
+            }
+
+            if !docs.is_empty() {
+                if is_after_blank_line {
+                    docs.push(Doc::HardLine);
+                }
+                if is_after_comment || is_after_blank_line {
+                    docs.push(Doc::HardLine);
+                } else {
+                    docs.push(Doc::Line);
+                }
+            }
+
+            docs.push(self.to_doc(term));
+            is_after_comment = matches!(
+                trivia(term),
+                Some(Trivia::Comment(text) | Trivia::TrailingComment(text)) if !is_inline_comment(text)
+            );
+            is_after_blank_line = false;
+        }
+
+        // The closing delimiter can't be in the same line of a comment.
+        if is_after_comment {
+            docs.push(Doc::HardLine);
+        }
+
+        Doc::Concat(docs)
+    }
+
+    fn list_to_doc(&self, elements: &[Term]) -> Doc {
+        let Some((head, tail)) = elements.split_first() else {
+            return Doc::text("()");
+        };
+
+        // The comments between the prefix and the term are kept, like in `'#_a b`.
+        let prefixed = tail
+            .split_last()
+            .filter(|(term, comments)| trivia(term).is_none() && comments.iter().all(is_trivia));
+        if let Some((prefix, (term, comments))) = reader_macro_prefix(head).zip(prefixed) {
+            let mut docs = vec![Doc::text(prefix)];
+            for comment in comments {
+                if let Some(Trivia::Comment(text) | Trivia::TrailingComment(text)) = trivia(comment)
+                {
+                    docs.push(Doc::text(text.clone()));
+                    docs.push(if is_inline_comment(text) {
+                        Doc::text(" ")
+                    } else {
+                        Doc::HardLine
+                    });
+                }
+            }
+            docs.push(self.to_doc(term));
+            return Doc::Concat(docs);
+        }
+
+        let name = match identifier_name(head) {
+            Some(name) => name,
+            None => {
+                let elements = self.sequence(elements).align();
+                return Doc::Concat(vec![Doc::text("("), elements, Doc::text(")")]).group();
+            }
+        };
+
+        if tail.is_empty() {
+            return Doc::text(format!("({name})"));
+        }
+
+        // Special forms keep their header in the same line of the head, and indent the
+        // body, like `(fun* name [a b]` and the body in the next line.
+        let special_form = self.special_forms.get(name).map(|&header| {
+            let header = header.min(tail.len());
+            tail.split_at(header)
+        });
+        if let Some((header, body)) = special_form.filter(|(header, _)| {
+            // Comments in the header can't be kept in the same line of the head.
+            header.iter().all(|term| trivia(term).is_none())
+        }) {
+            let mut head = vec![Doc::text(format!("({name}"))];
+            for term in header {
+                head.push(Doc::text(" "));
+                head.push(self.to_doc(term));
+            }
+
+            // A trailing comment after the header stays in the line of the head.
+            let mut body = body;
+            let mut separator = Doc::Line;
+            if let Some(Trivia::TrailingComment(text)) = body.first().and_then(trivia) {
+                head.push(Doc::text(format!(" {text}")));
+                body = &body[1..];
+                separator = Doc::HardLine;
+            }
+            if !body.is_empty() {
+                let body = Doc::Concat(vec![separator, self.sequence(body)]);
+                head.push(body.nest(self.indent));
+            } else if separator == Doc::HardLine {
+                head.push(Doc::HardLine);
+            }
+            head.push(Doc::text(")"));
+
+            return Doc::Concat(head).align().group();
+        }
+
+        // Applications align their arguments with the first one.
+        Doc::Concat(vec![
+            Doc::text(format!("({name} ")),
+            self.sequence(tail).align(),
+            Doc::text(")"),
+        ])
+        .group()
+    }
+}
+
+/// Returns the prefix of the reader macro that desugars into the form, like `'` for
+/// `(quote x)`.
+fn reader_macro_prefix(head: &Term) -> Option<&'static str> {
+    match identifier_name(head)? {
+        "quote" => Some("'"),
+        "quasiquote" => Some("`"),
+        "unquote" => Some(","),
+        "unquote-splicing" => Some(",@"),
+        _ => None,
+    }
+}
+
+fn trivia(term: &Term) -> Option<&Trivia> {
+    match term {
+        Term::SrcPos(_, term) => trivia(term),
+        Term::Trivia(trivia) => Some(trivia),
+        _ => None,
+    }
+}
+
+fn is_trivia(term: &Term) -> bool {
+    trivia(term).is_some()
+}
+
+/// Returns if the text of a comment can be followed by a term in the same line, like the
+/// datum comments or the block comments that don't span multiple lines.
+fn is_inline_comment(text: &str) -> bool {
+    text.starts_with('#') && !text.contains('\n')
+}
+
+fn identifier_name(term: &Term) -> Option<&str> {
+    match term {
+        Term::SrcPos(_, term) => identifier_name(term),
+        Term::Identifier(name) => Some(name.as_str()),
+        _ => None,
+    }
+}
+
+fn atom_to_string(term: &Term) -> String {
+    match term {
+        Term::Atom(s) => format!(":{s}"),
+        Term::Identifier(s) => s.to_string(),
+        Term::Int(n) => n.to_string(),
+        Term::Float(n) => format!("{n:?}"),
+        Term::BigInt(n) => n.to_string(),
+        Term::Ratio(n) => n.to_string(),
+        Term::String(s) => escape_string(s),
+        Term::Char(c) => escape_char(*c),
+        Term::Error => "#<error>".to_string(),
+        Term::SrcPos(_, term) => atom_to_string(term),
+        Term::Verbatim(text, _) | Term::Opaque(text) => text.to_string(),
+        Term::List(_) | Term::Vec(_) | Term::Map(_) | Term::Trivia(_) => {
+            unreachable!("not an atom")
+        }
+    }
+}
+
+impl Term {
+    /// Pretty prints the term with the given style.
+    pub fn pretty(&self, style: &Style) -> String {
+        style.to_doc(self).render(style.width)
+    }
+}
+
+/// Tests for the layout of terms.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_program_lossless, parse_sexpr};
+
+    fn pretty(source: &str, width: usize) -> String {
+        let style = Style {
+            width,
+            ..Style::default()
+        };
+        parse_sexpr(source).unwrap().pretty(&style)
+    }
+
+    #[test]
+    fn prints_flat_when_it_fits() {
+        assert_eq!(pretty("(f   a [1 2]  \"b\")", 80), "(f a [1 2] \"b\")");
+        assert_eq!(pretty("()", 80), "()");
+        assert_eq!(pretty("(f)", 80), "(f)");
+        assert_eq!(pretty("{:a  1 :b [2]}", 80), "{:a 1 :b [2]}");
+    }
+
+    #[test]
+    fn aligns_arguments_when_breaking() {
+        assert_eq!(
+            pretty("(print (add 1 2) [alpha beta gamma])", 20),
+            "(print (add 1 2)\n       [alpha\n        beta\n        gamma])"
+        );
+    }
+
+    #[test]
+    fn indents_special_forms() {
+        assert_eq!(
+            pretty("(fun* add [a b] (+ a b))", 20),
+            "(fun* add [a b]\n  (+ a b))"
+        );
+        assert_eq!(
+            pretty("(def* x (fun* id [a] a))", 20),
+            "(def* x\n  (fun* id [a] a))"
+        );
+    }
+
+    #[test]
+    fn prints_reader_macros() {
+        assert_eq!(
+            pretty("(quasiquote (a (unquote b) ,@c))", 80),
+            "`(a ,b ,@c)"
+        );
+        assert_eq!(pretty("'x", 80), "'x");
+    }
+
+    #[test]
+    fn respects_width_with_trailing_delimiters() {
+        // `(f aa bb)` fits in 10 columns, but not with the closing delimiter of the vector.
+        assert_eq!(pretty("[(f aa bb)]", 11), "[(f aa bb)]");
+        assert_eq!(pretty("[(f aa bb)]", 10), "[(f aa\n    bb)]");
+    }
+
+    fn format(source: &str) -> String {
+        Style::default().format_program(&parse_program_lossless(source).unwrap())
+    }
+
+    #[test]
+    fn formats_preserving_comments() {
+        let source = ";; header\n\n\n\n(def*   x ; the x\n  1)\n(f a ; first\n   b)\n\n[1 ; one\n]";
+        let expected = ";; header\n\n(def* x ; the x\n  1)\n(f a ; first\n   b)\n\n[1 ; one\n ]\n";
+
+        assert_eq!(format(source), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn formats_preserving_number_spelling() {
+        let source = "(def* a [0xFF 1_000_000 1e3 -1/2 1.50 9223372036854775808])";
+
+        assert_eq!(format(source), format!("{source}\n"));
+        assert_eq!(
+            pretty(source, 80),
+            "(def* a [255 1000000 1000.0 -1/2 1.5 9223372036854775808])"
+        );
+    }
+
+    #[test]
+    fn formats_preserving_string_and_char_spelling() {
+        let source = "(def* a [\"\\u{41}\\t\" \\u{41} \\newline \\a])";
+        assert_eq!(format(source), format!("{source}\n"));
+        assert_eq!(pretty(source, 80), "(def* a [\"A\\t\" \\A \\newline \\a])");
+
+        // The terms after a string with line breaks continue in its last line.
+        let source = "(f \"first\nsecond\" b\n   c)";
+        assert_eq!(format(source), "(f \"first\nsecond\" b c)\n");
+        assert_eq!(
+            format("[\"first\nsecond\" bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb]"),
+            "[\"first\nsecond\"\n bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb]\n"
+        );
+    }
+
+    #[test]
+    fn formats_inline_comments() {
+        assert_eq!(format("(def* a [1 #_2 3])"), "(def* a [1 #_2 3])\n");
+        assert_eq!(format("(f #| a |# b)"), "(f #| a |# b)\n");
+        assert_eq!(format("(def* w '#_b a)"), "(def* w '#_b a)\n");
+        assert_eq!(format("['#| c |#a ' b]"), "['#| c |# a 'b]\n");
+        assert_eq!(format("'; c\na"), "'; c\na\n");
+    }
+
+    #[test]
+    fn formats_own_line_comments() {
+        let source = "(fun* f [a]\n  ; returns a\n\n  a)";
+        assert_eq!(format(source), "(fun* f [a]\n  ; returns a\n\n  a)\n");
+        assert_eq!(format(""), "");
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead},
+};
+
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::{
+    lexer::is_identifier_char,
+    parser::{ParseError, Parser},
+    source::{self, FileId},
+    Term,
+};
+
+/// Errors that can occur while reading, either from the input or from the parser.
+#[derive(Error, Diagnostic, Debug)]
+pub enum ReadError {
+    #[error("failed to read the input: {0}")]
+    #[diagnostic(code(soft::reader::io))]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Parse(#[from] ParseError),
+}
+
+/// Tracks the brackets, strings and block comments that are open in the lines read so
+/// far, so the reader only parses the buffer when it can hold complete forms.
+///
+/// It's only an approximation of the lexer: if it's wrong, the parser asks for more lines
+/// or the whole buffer is parsed at the end of the input.
+#[derive(Debug, Default)]
+struct Nesting {
+    depth: usize,
+    comments: usize,
+    string: bool,
+}
+
+impl Nesting {
+    fn scan(&mut self, line: &str) {
+        let mut chars = line.chars().peekable();
+        let mut previous = ' ';
+
+        while let Some(c) = chars.next() {
+            if self.string {
+                match c {
+                    '\\' => drop(chars.next()),
+                    '"' => self.string = false,
+                    _ => {}
+                }
+            } else if self.comments > 0 {
+                match (c, chars.peek()) {
+                    ('#', Some('|')) => {
+                        chars.next();
+                        self.comments += 1;
+                    }
+                    ('|', Some('#')) => {
+                        chars.next();
+                        self.comments -= 1;
+                    }
+                    _ => {}
+                }
+            } else {
+                match c {
+                    ';' => break,
+                    '"' => self.string = true,
+                    '#' if chars.peek() == Some(&'|') && !is_identifier_char(previous) => {
+                        chars.next();
+                        self.comments += 1;
+                    }
+                    // The first character of a character literal can be a bracket, like `\(`.
+                    '\\' => drop(chars.next()),
+                    '(' | '[' | '{' => self.depth += 1,
+                    ')' | ']' | '}' => self.depth = self.depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+
+            previous = c;
+        }
+    }
+
+    fn is_top_level(&self) -> bool {
+        self.depth == 0 && self.comments == 0 && !self.string
+    }
+}
+
+/// Reads the top-level forms of an input one at a time, like a pipe or a large data file.
+/// The lines are buffered until they hold complete forms, that are parsed and yielded in
+/// order, so the forms are available before the input ends.
+///
+/// The positions of the terms are the byte offsets in the whole input. If the reader has a
+/// registered file, the lines are appended to it as they're parsed, so the positions can
+/// be resolved, which keeps the whole input in memory.
+pub struct Reader<R> {
+    pub input: R,
+    pub file: FileId,
+
+    /// The lines read but not parsed yet.
+    buffer: String,
+
+    /// The position of the buffer in the input.
+    offset: usize,
+    nesting: Nesting,
+
+    /// The forms already parsed but not yielded yet, a single line can have many.
+    forms: VecDeque<Term>,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(input: R) -> Self {
+        Self::with_file(FileId::default(), input)
+    }
+
+    /// Creates a reader whose terms refer to a file registered in the
+    /// [crate::source::SourceMap], that should be empty as the input is appended to it.
+    pub fn with_file(file: FileId, input: R) -> Self {
+        Self {
+            input,
+            file,
+            buffer: String::new(),
+            offset: 0,
+            nesting: Nesting::default(),
+            forms: VecDeque::new(),
+        }
+    }
+
+    /// Parses the buffer into forms. If the input didn't end, a buffer that ends in the
+    /// middle of a form is kept to be completed by the next lines.
+    fn parse_buffer(&mut self, is_eof: bool) -> Result<(), ParseError> {
+        let result = Parser::with_file(self.file, &self.buffer).parse_program();
+        if let Err(ParseError::UnexpectedEndOfFile(_)) = result {
+            if !is_eof {
+                return Ok(());
+            }
+        }
+
+        source::append_file(self.file, &self.buffer);
+        let offset = self.offset;
+        self.offset += self.buffer.len();
+        self.buffer.clear();
+        self.nesting = Nesting::default();
+
+        match result {
+            Ok(terms) => {
+                let terms = terms.into_iter().map(|term| shift(term, offset));
+                self.forms.extend(terms);
+                Ok(())
+            }
+            Err(mut error) => {
+                error.src_pos_mut().shift(offset);
+                Err(error)
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<Term, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(form) = self.forms.pop_front() {
+                return Some(Ok(form));
+            }
+
+            let start = self.buffer.len();
+            let is_eof = match self.input.read_line(&mut self.buffer) {
+                Ok(n) => n == 0,
+                Err(error) => return Some(Err(error.into())),
+            };
+
+            if is_eof && self.buffer.trim().is_empty() {
+                return None;
+            }
+
+            self.nesting.scan(&self.buffer[start..]);
+            if is_eof || self.nesting.is_top_level() {
+                if let Err(error) = self.parse_buffer(is_eof) {
+                    return Some(Err(error.into()));
+                }
+            }
+        }
+    }
+}
+
+/// Moves the positions of the term and its children `offset` bytes forward.
+fn shift(term: Term, offset: usize) -> Term {
+    let shift_all = |terms: &[Term]| terms.iter().cloned().map(|t| shift(t, offset)).collect();
+
+    match term {
+        Term::SrcPos(mut src_pos, box term) => {
+            src_pos.shift(offset);
+            Term::SrcPos(src_pos, Box::new(shift(term, offset)))
+        }
+        Term::List(terms) => Term::List(shift_all(&terms)),
+        Term::Vec(terms) => Term::Vec(shift_all(&terms)),
+        Term::Map(terms) => Term::Map(shift_all(&terms)),
+        term => term,
+    }
+}
+
+/// Reads every top-level form of the input, like [crate::parser::parse_program].
+pub fn read_program(input: impl BufRead) -> Result<Vec<Term>, ReadError> {
+    Reader::new(input).collect()
+}
+
+/// Tests for the streaming reader.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::parse_program, SrcPos};
+
+    #[test]
+    fn reads_forms_one_at_a_time() {
+        let source = "(a\n [b \"c\n)\"]) 1 2\n'd #| (e\n |# \\( ; (f\n{:g 1 #_\nh}\n";
+        let mut reader = Reader::new(source.as_bytes());
+
+        let unboxed = |term: Term| term.unbox().to_string();
+        let forms = reader.by_ref().map(|term| unboxed(term.unwrap()));
+        assert_eq!(forms.collect::<Vec<_>>(), [
+            "(a [b \"c\\n)\"])",
+            "1",
+            "2",
+            "'d",
+            "\\(",
+            "{:g 1}"
+        ]);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn keeps_positions_of_whole_input() {
+        let source = "(a b)\n\n  [c\n d] e\n";
+        let terms = read_program(source.as_bytes()).unwrap();
+
+        assert_eq!(terms, parse_program(source).unwrap());
+    }
+
+    #[test]
+    fn fails_on_invalid_forms() {
+        let source = "(a)\n)\n(b\n";
+        let mut reader = Reader::new(source.as_bytes());
+
+        assert!(reader.next().unwrap().is_ok());
+        let Some(Err(ReadError::Parse(error))) = reader.next() else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(
+            error,
+            ParseError::UnexpectedClosingBracket(')', SrcPos {
+                byte: 4..5,
+                file: FileId::default(),
+            })
+        );
+
+        let Some(Err(ReadError::Parse(error))) = reader.next() else {
+            panic!("expected a parse error");
+        };
+        assert!(matches!(error, ParseError::UnexpectedEndOfFile(_)));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn registers_read_lines() {
+        let file = source::add_file("<stdin>", "");
+        let mut reader = Reader::with_file(file, "(def* a 1)\n(fun* f\n  [x] x)\n  )\n".as_bytes());
+
+        assert!(reader.next().unwrap().is_ok());
+        let term = reader.next().unwrap().unwrap();
+        assert_eq!(term.src_pos().file, file);
+        assert_eq!(
+            term.src_pos().snippet().as_deref(),
+            Some("(fun* f\n  [x] x)")
+        );
+
+        let Some(Err(ReadError::Parse(error))) = reader.next() else {
+            panic!("expected a parse error");
+        };
+        let location = error.src_pos().location().unwrap();
+        assert_eq!(location.to_string(), "<stdin>:4:3");
+        assert_eq!(
+            file.source().unwrap().text.as_str(),
+            "(def* a 1)\n(fun* f\n  [x] x)\n  )\n"
+        );
+    }
+
+    #[test]
+    fn registers_large_inputs() {
+        let file = source::add_file("<stdin>", "");
+        let input = (0..10_000)
+            .map(|n| format!("(a\n {n})\n"))
+            .collect::<String>();
+        let terms = Reader::with_file(file, input.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(terms.len(), 10_000);
+        let location = terms.last().unwrap().src_pos().location().unwrap();
+        assert_eq!(location.to_string(), "<stdin>:19999:1");
+        assert_eq!(file.source().unwrap().text, input);
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    fmt::Display,
+    ops::Range,
+    sync::{Arc, RwLock},
+};
+
+/// The global registry of source files, every [crate::SrcPos] refers to one of them by
+/// its [FileId].
+static SOURCE_MAP: RwLock<SourceMap> = RwLock::new(SourceMap::new());
+
+/// An identifier of a file in the [SourceMap]. The default identifier doesn't refer to
+/// any file, and is used for terms that weren't read from a registered source.
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FileId(pub usize);
+
+/// A source file, with its name, text and the offsets where each line starts.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub id: FileId,
+    pub name: String,
+    pub text: String,
+    line_starts: Vec<usize>,
+}
+
+/// A resolved position in a file, it's displayed like `foo.soft:12:5`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Registry of source files.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<Arc<SourceFile>>,
+}
+
+impl SourceMap {
+    pub const fn new() -> Self {
+        Self { files: Vec::new() }
+    }
+
+    /// Registers a new file and returns its identifier.
+    pub fn add(&mut self, name: impl Into<String>, text: impl Into<String>) -> FileId {
+        let id = FileId(self.files.len() + 1);
+        self.files
+            .push(Arc::new(SourceFile::new(id, name.into(), text.into())));
+
+        id
+    }
+
+    /// Replaces the text of a registered file, keeping its identifier and name. It's used
+    /// by the inputs that are read in pieces, like the lines of a repl, so they don't
+    /// register a new file for each one.
+    pub fn replace(&mut self, id: FileId, text: impl Into<String>) {
+        let Some(file) =
+            id.0.checked_sub(1)
+                .and_then(|index| self.files.get_mut(index))
+        else {
+            return;
+        };
+
+        *file = Arc::new(SourceFile::new(id, file.name.clone(), text.into()));
+    }
+
+    /// Appends text to a registered file. It's used by the inputs that are read in chunks,
+    /// like the standard input, so the positions in the whole input can be resolved.
+    /// The text is extended in place, and only the lines of the new text are indexed.
+    pub fn append(&mut self, id: FileId, text: &str) {
+        let Some(file) =
+            id.0.checked_sub(1)
+                .and_then(|index| self.files.get_mut(index))
+        else {
+            return;
+        };
+
+        let file = Arc::make_mut(file);
+        let offset = file.text.len();
+        let line_starts = text
+            .match_indices('\n')
+            .map(|(index, _)| offset + index + 1);
+        file.line_starts.extend(line_starts);
+        file.text.push_str(text);
+    }
+
+    /// Returns the file with the given identifier, if it's registered.
+    pub fn get(&self, id: FileId) -> Option<Arc<SourceFile>> {
+        self.files.get(id.0.checked_sub(1)?).cloned()
+    }
+}
+
+impl SourceFile {
+    fn new(id: FileId, name: String, text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+
+        Self {
+            id,
+            name,
+            text,
+            line_starts,
+        }
+    }
+
+    /// Returns the 1-based line and column of a byte offset, columns are counted in
+    /// characters.
+    pub fn line_column(&self, byte: usize) -> (usize, usize) {
+        let byte = byte.min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= byte) - 1;
+        let line_start = self.line_starts[line];
+        let column = self
+            .text
+            .get(line_start..byte)
+            .map_or(0, |prefix| prefix.chars().count());
+
+        (line + 1, column + 1)
+    }
+
+    /// Resolves a byte offset into a [Location].
+    pub fn location(&self, byte: usize) -> Location {
+        let (line, column) = self.line_column(byte);
+        Location {
+            name: self.name.clone(),
+            line,
+            column,
+        }
+    }
+
+    /// Returns the text in the byte range, if it's valid for this file.
+    pub fn snippet(&self, byte: Range<usize>) -> Option<&str> {
+        self.text.get(byte)
+    }
+}
+
+impl FileId {
+    /// Returns the registered file of this identifier.
+    pub fn source(self) -> Option<Arc<SourceFile>> {
+        get_file(self)
+    }
+}
+
+/// Registers a new file in the global [SourceMap].
+pub fn add_file(name: impl Into<String>, text: impl Into<String>) -> FileId {
+    SOURCE_MAP.write().unwrap().add(name, text)
+}
+
+/// Replaces the text of a file in the global [SourceMap].
+pub fn replace_file(id: FileId, text: impl Into<String>) {
+    SOURCE_MAP.write().unwrap().replace(id, text)
+}
+
+/// Appends text to a file in the global [SourceMap].
+pub fn append_file(id: FileId, text: &str) {
+    SOURCE_MAP.write().unwrap().append(id, text)
+}
+
+/// Returns a file of the global [SourceMap].
+pub fn get_file(id: FileId) -> Option<Arc<SourceFile>> {
+    SOURCE_MAP.read().unwrap().get(id)
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.name, self.line, self.column)
+    }
+}
+
+/// Tests for the resolution of positions in the source map.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, SrcPos, Term};
+
+    #[test]
+    fn resolves_line_and_column() {
+        let mut source_map = SourceMap::new();
+        let id = source_map.add("foo.soft", "(a\n  bé c)\n");
+        let file = source_map.get(id).unwrap();
+
+        assert_eq!(file.line_column(0), (1, 1));
+        assert_eq!(file.line_column(3), (2, 1));
+        assert_eq!(file.line_column(8), (2, 5));
+        assert_eq!(file.location(8).to_string(), "foo.soft:2:5");
+        assert_eq!(file.snippet(5..8), Some("bé"));
+        assert!(source_map.get(FileId::default()).is_none());
+    }
+
+    #[test]
+    fn replaces_text_of_files() {
+        let mut source_map = SourceMap::new();
+        let id = source_map.add("<repl>", "(a b)");
+        source_map.replace(id, "c\n  d");
+        let file = source_map.get(id).unwrap();
+
+        assert_eq!(source_map.files.len(), 1);
+        assert_eq!(file.location(4).to_string(), "<repl>:2:3");
+        assert_eq!(file.snippet(4..5), Some("d"));
+    }
+
+    #[test]
+    fn resolves_parsed_positions() {
+        let file = add_file("bar.soft", "; comment\n(def* x\n  \"y\")");
+        let text = file.source().unwrap().text.clone();
+        let term = Parser::with_file(file, &text).parse().unwrap();
+        let Term::SrcPos(_, box Term::List(elements)) = term else {
+            panic!("expected list");
+        };
+        let Term::SrcPos(src_pos, _) = &elements[2] else {
+            panic!("expected position");
+        };
+
+        assert_eq!(src_pos.file, file);
+        assert_eq!(src_pos.location().unwrap().to_string(), "bar.soft:3:3");
+        assert_eq!(src_pos.snippet().as_deref(), Some("\"y\""));
+        assert_eq!(SrcPos::default().to_string(), "0..0");
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, RwLock},
+};
+
+use crate::{
+    symbol::Symbol, Apply, Def, DefMacro, Expr, Fun, List, Quasiquote, Quote, Recur, Result,
+    SemanticError, Term,
+};
+
+/// The global registry of special forms, keyed by their names. It starts with the
+/// [SPECIAL_FORMS], and the host can register new ones with [register].
+static REGISTRY: LazyLock<RwLock<HashMap<Symbol, SpecialForm>>> = LazyLock::new(|| {
+    let forms = SPECIAL_FORMS
+        .iter()
+        .map(|form| (Symbol::intern(form.name), *form));
+    RwLock::new(forms.collect())
+});
+
+/// A special form, it lowers the lists whose head is its name into an expression, instead
+/// of them being applications.
+#[derive(Clone, Copy)]
+pub struct SpecialForm {
+    pub name: &'static str,
+
+    /// The number of arguments after the head, it's checked before lowering. If it's
+    /// `None`, any number of arguments is accepted.
+    pub arity: Option<usize>,
+
+    /// Lowers the whole list, with the head, into an expression.
+    pub lower: fn(Term) -> Result<Expr>,
+}
+
+/// Every special form that's registered by default.
+pub const SPECIAL_FORMS: &[SpecialForm] = &[
+    DefMacro::SPECIAL_FORM,
+    Def::SPECIAL_FORM,
+    Recur::SPECIAL_FORM,
+    Fun::SPECIAL_FORM,
+    Quote::SPECIAL_FORM,
+    Quasiquote::SPECIAL_FORM,
+    List::SPECIAL_FORM,
+    Apply::SPECIAL_FORM,
+];
+
+impl SpecialForm {
+    /// Checks the arity of the list, and lowers it into an expression.
+    pub fn apply(&self, term: Term) -> Result<Expr> {
+        let (_, arguments) = term
+            .split()
+            .ok_or_else(|| SemanticError::InvalidExpression(term.src_pos()))?;
+        match self.arity {
+            Some(arity) if arguments.len() != arity => Err(SemanticError::IncorrectArity(
+                self.name,
+                arity,
+                arguments.len(),
+                term.src_pos(),
+            )),
+            _ => (self.lower)(term),
+        }
+    }
+}
+
+/// Registers a special form, replacing the one with the same name if there's any.
+pub fn register(form: SpecialForm) {
+    REGISTRY
+        .write()
+        .unwrap()
+        .insert(Symbol::intern(form.name), form);
+}
+
+/// Unregisters the special form with the name, returning it if it was registered. Its
+/// lists are lowered into applications again.
+pub fn unregister(name: &str) -> Option<SpecialForm> {
+    REGISTRY.write().unwrap().remove(&Symbol::intern(name))
+}
+
+/// Returns the special form with the name.
+pub fn get(name: Symbol) -> Option<SpecialForm> {
+    REGISTRY.read().unwrap().get(&name).copied()
+}
+
+/// Tests for the registry of special forms.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::parse_sexpr, source::FileId, SrcPos};
+
+    fn lower(source: &str) -> Result<Expr> {
+        parse_sexpr(source).unwrap().try_into()
+    }
+
+    #[test]
+    fn lowers_builtin_forms() {
+        assert!(matches!(lower("(fun* f [a] a)"), Ok(Expr::Fun(_))));
+        assert!(matches!(lower("(def* a 1)"), Ok(Expr::Def(_))));
+        assert!(matches!(lower("(list a b)"), Ok(Expr::List(_))));
+        assert!(matches!(lower("(apply f a)"), Ok(Expr::Apply(_))));
+        assert!(matches!(lower("(f a)"), Ok(Expr::Apply(_))));
+        assert!(matches!(lower("[a b]"), Ok(Expr::List(_))));
+        assert!(matches!(lower("()"), Ok(Expr::Literal(_))));
+    }
+
+    #[test]
+    fn fails_on_incorrect_arity() {
+        let error = lower("(fun* f)").unwrap_err();
+        assert_eq!(error.to_string(), "fun* expects 3 arguments, got 1");
+        assert_eq!(
+            error,
+            SemanticError::IncorrectArity("fun*", 3, 1, SrcPos {
+                byte: 0..8,
+                file: FileId::default(),
+            })
+        );
+
+        // The position is the one of the malformed form, even when it's nested.
+        let Ok(Expr::Apply(apply)) = lower("(f a\n  (quote a b))") else {
+            panic!("expected an application");
+        };
+        let error = apply.spine().unwrap_err();
+        assert_eq!(
+            error,
+            SemanticError::IncorrectArity("quote", 1, 2, SrcPos {
+                byte: 7..18,
+                file: FileId::default(),
+            })
+        );
+    }
+
+    #[test]
+    fn names_the_form_in_errors() {
+        let Ok(Expr::Fun(fun)) = lower("(fun* f a a)") else {
+            panic!("expected a function");
+        };
+        let error = fun.parameters().unwrap_err();
+        assert_eq!(error.to_string(), "invalid list in fun*");
+        assert_eq!(
+            error,
+            SemanticError::InvalidList("fun*", SrcPos {
+                byte: 8..9,
+                file: FileId::default(),
+            })
+        );
+        assert_eq!(
+            Term::from(Expr::from(error)).unbox().to_string(),
+            "[:error/invalid-list \"fun*\"]"
+        );
+    }
+
+    /// Unregisters a special form when it's dropped, so the registry is restored even if
+    /// the test fails.
+    struct Registered(&'static str);
+
+    impl Drop for Registered {
+        fn drop(&mut self) {
+            unregister(self.0);
+        }
+    }
+
+    #[test]
+    fn registers_special_forms() {
+        assert!(matches!(lower("(test/vec a b)"), Ok(Expr::Apply(_))));
+
+        let registered = Registered("test/vec");
+        register(SpecialForm {
+            name: "test/vec",
+            arity: Some(2),
+            lower: |term| {
+                let (_, elements) = term.split().unwrap();
+                let elements = Term::Vec(elements.into());
+                Ok(List(term.transport(elements)).into())
+            },
+        });
+
+        let Ok(Expr::List(list)) = lower("(test/vec a b)") else {
+            panic!("expected a list");
+        };
+        assert_eq!(list.elements().unwrap().len(), 2);
+        assert!(lower("(test/vec a)").is_err());
+
+        drop(registered);
+        assert!(get(Symbol::intern("test/vec")).is_none());
+        assert!(matches!(lower("(test/vec a b)"), Ok(Expr::Apply(_))));
+    }
+}
@@ -0,0 +1,90 @@
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    sync::{LazyLock, RwLock},
+};
+
+/// The global interner of symbols. The strings are leaked, so they can be borrowed for the
+/// rest of the program, like the names in a symbol table.
+static INTERNER: LazyLock<RwLock<Interner>> = LazyLock::new(Default::default);
+
+/// An interned string, used for identifiers, atoms and keywords. It's a copyable id, so
+/// it's compared and hashed as an integer, and the string is only looked up to print it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+/// Table of the interned strings, indexed by the id of their symbols.
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    strings: Vec<&'static str>,
+}
+
+impl Symbol {
+    /// Returns the symbol of the string, interning it if it's the first time.
+    pub fn intern(string: &str) -> Symbol {
+        if let Some(symbol) = INTERNER.read().unwrap().symbols.get(string) {
+            return *symbol;
+        }
+
+        // Another thread could have interned the string between the locks.
+        let mut interner = INTERNER.write().unwrap();
+        if let Some(symbol) = interner.symbols.get(string) {
+            return *symbol;
+        }
+
+        let string: &'static str = Box::leak(string.into());
+        let symbol = Symbol(interner.strings.len() as u32);
+        interner.strings.push(string);
+        interner.symbols.insert(string, symbol);
+        symbol
+    }
+
+    /// Returns the interned string.
+    pub fn as_str(self) -> &'static str {
+        INTERNER.read().unwrap().strings[self.0 as usize]
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(string: &str) -> Self {
+        Symbol::intern(string)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(string: String) -> Self {
+        Symbol::intern(&string)
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+/// Tests for the interning of symbols.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interns_strings() {
+        let symbol = Symbol::intern("interns-strings");
+
+        assert_eq!(symbol, Symbol::from("interns-strings".to_string()));
+        assert_ne!(symbol, Symbol::intern("interns-strings?"));
+        assert_eq!(symbol.as_str(), "interns-strings");
+        assert_eq!(
+            format!("{symbol} {symbol:?}"),
+            "interns-strings \"interns-strings\""
+        );
+    }
+}